@@ -0,0 +1,131 @@
+use crate::*;
+use near_sdk::json_types::{ValidAccountId, U128};
+
+/// Owner -> spender allowance key, `:` can't appear in account ids.
+pub(crate) fn allowance_key(owner_id: &AccountId, spender_id: &AccountId) -> String {
+    format!("{}:{}", owner_id, spender_id)
+}
+
+/// Prefix of the owner's set of spenders.
+fn spenders_prefix(owner_id: &AccountId) -> Vec<u8> {
+    [b"q".as_ref(), &env::sha256(owner_id.as_bytes())].concat()
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sets the amount `spender_id` can move out of the predecessor's account with `ft_transfer_from`.
+    /// A new allowance requires a deposit of `ft_allowance_storage_cost` (the rest is refunded),
+    /// updating an existing one requires exactly 1 yoctoNEAR.
+    #[payable]
+    pub fn ft_approve(&mut self, spender_id: ValidAccountId, amount: U128) {
+        let owner_id = env::predecessor_account_id();
+        assert!(self.accounts.contains_key(&owner_id), "The account is not registered");
+        assert_ne!(&owner_id, spender_id.as_ref(), "Owner and spender should be different");
+        let key = allowance_key(&owner_id, spender_id.as_ref());
+        if self.allowances.get(&key).is_some() {
+            assert_one_yocto();
+        } else {
+            let storage_cost = self.ft_allowance_storage_cost().0;
+            let deposit = env::attached_deposit();
+            assert!(
+                deposit >= storage_cost,
+                "Requires attached deposit of at least {} to cover the allowance storage",
+                storage_cost
+            );
+            if deposit > storage_cost {
+                Promise::new(owner_id.clone()).transfer(deposit - storage_cost);
+            }
+        }
+        self.internal_insert_allowance(&owner_id, spender_id.as_ref(), amount.into());
+    }
+
+    /// Removes the allowance and refunds its storage deposit to the owner.
+    /// `storage_unregister` removes all of the owner's allowances and refunds them too.
+    #[payable]
+    pub fn ft_revoke(&mut self, spender_id: ValidAccountId) {
+        assert_one_yocto();
+        let owner_id = env::predecessor_account_id();
+        if !self.internal_remove_allowance(&owner_id, spender_id.as_ref()) {
+            env::panic(b"No allowance for this spender");
+        }
+        Promise::new(owner_id).transfer(self.ft_allowance_storage_cost().0);
+    }
+
+    /// Spender moves tokens from `owner_id` to `receiver_id`, the allowance goes down by `amount`.
+    #[payable]
+    pub fn ft_transfer_from(
+        &mut self,
+        owner_id: ValidAccountId,
+        receiver_id: ValidAccountId,
+        amount: U128,
+        memo: Option<String>,
+    ) {
+        assert_one_yocto();
+        let spender_id = env::predecessor_account_id();
+        let amount: Balance = amount.into();
+        assert!(amount > 0, "The amount should be a positive number");
+        let key = allowance_key(owner_id.as_ref(), &spender_id);
+        let allowance = self.allowances.get(&key).expect("No allowance for this spender");
+        if let Some(new_allowance) = allowance.checked_sub(amount) {
+            self.allowances.insert(&key, &new_allowance);
+        } else {
+            env::panic(b"The amount exceeds the allowance");
+        }
+        self.internal_transfer(owner_id.as_ref(), receiver_id.as_ref(), amount, memo);
+    }
+
+    /// views
+
+    pub fn ft_allowance(&self, owner_id: ValidAccountId, spender_id: ValidAccountId) -> U128 {
+        self.allowances
+            .get(&allowance_key(owner_id.as_ref(), spender_id.as_ref()))
+            .unwrap_or(0)
+            .into()
+    }
+
+    pub fn ft_allowance_storage_cost(&self) -> U128 {
+        (Balance::from(self.allowance_storage_usage) * STORAGE_PRICE_PER_BYTE).into()
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_insert_allowance(&mut self, owner_id: &AccountId, spender_id: &AccountId, amount: Balance) {
+        self.allowances.insert(&allowance_key(owner_id, spender_id), &amount);
+        let mut spenders = self
+            .spenders_by_owner
+            .get(owner_id)
+            .unwrap_or_else(|| UnorderedSet::new(spenders_prefix(owner_id)));
+        spenders.insert(spender_id);
+        self.spenders_by_owner.insert(owner_id, &spenders);
+    }
+
+    /// Returns false if there was no allowance.
+    pub(crate) fn internal_remove_allowance(&mut self, owner_id: &AccountId, spender_id: &AccountId) -> bool {
+        if self.allowances.remove(&allowance_key(owner_id, spender_id)).is_none() {
+            return false;
+        }
+        let mut spenders = self.spenders_by_owner.get(owner_id).expect("No spenders for this owner");
+        spenders.remove(spender_id);
+        if spenders.is_empty() {
+            self.spenders_by_owner.remove(owner_id);
+        } else {
+            self.spenders_by_owner.insert(owner_id, &spenders);
+        }
+        true
+    }
+
+    /// Removes all of the owner's allowances, returns how many there were.
+    pub(crate) fn internal_remove_allowances(&mut self, owner_id: &AccountId) -> u64 {
+        let mut spenders = if let Some(spenders) = self.spenders_by_owner.remove(owner_id) {
+            spenders
+        } else {
+            return 0;
+        };
+        for spender_id in spenders.iter() {
+            self.allowances.remove(&allowance_key(owner_id, &spender_id));
+        }
+        let count = spenders.len();
+        spenders.clear();
+        count
+    }
+}
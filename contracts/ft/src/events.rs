@@ -0,0 +1,73 @@
+//! NEP-297 events for mints, transfers and burns.
+
+use crate::*;
+use near_sdk::serde::Serialize;
+
+pub const FT_STANDARD_NAME: &str = "nep141";
+pub const FT_STANDARD_VERSION: &str = "1.0.0";
+const EVENT_JSON_PREFIX: &str = "EVENT_JSON:";
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+pub enum FtEventKind {
+    FtMint(Vec<FtMintLog>),
+    FtTransfer(Vec<FtTransferLog>),
+    FtBurn(Vec<FtBurnLog>),
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtEvent {
+    pub standard: String,
+    pub version: String,
+    #[serde(flatten)]
+    pub event: FtEventKind,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtMintLog {
+    pub owner_id: AccountId,
+    pub amount: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtTransferLog {
+    pub old_owner_id: AccountId,
+    pub new_owner_id: AccountId,
+    pub amount: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtBurnLog {
+    pub owner_id: AccountId,
+    pub amount: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+impl FtEventKind {
+    pub(crate) fn emit(self) {
+        let event = FtEvent {
+            standard: FT_STANDARD_NAME.to_string(),
+            version: FT_STANDARD_VERSION.to_string(),
+            event: self,
+        };
+        env::log(
+            format!(
+                "{}{}",
+                EVENT_JSON_PREFIX,
+                near_sdk::serde_json::to_string(&event).unwrap()
+            )
+            .as_bytes(),
+        );
+    }
+}
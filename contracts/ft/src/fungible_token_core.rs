@@ -91,7 +91,7 @@ trait FungibleTokenResolverExt {
     ) -> U128;
 }
 
-trait FungibleTokenResolver {
+pub trait FungibleTokenResolver {
     fn ft_resolve_transfer(
         &mut self,
         sender_id: AccountId,
@@ -152,16 +152,20 @@ impl FungibleTokenCore for Contract {
 
 #[near_bindgen]
 impl FungibleTokenResolver for Contract {
+    /// Returns the amount of tokens used by the receiver, as NEP-141 requires: the transferred amount minus
+    /// what was refunded to the sender. Unused tokens the receiver no longer holds can't be refunded, and
+    /// a refund to a deleted sender is burned, both count as used.
+    #[private]
     fn ft_resolve_transfer(
         &mut self,
         sender_id: AccountId,
         receiver_id: AccountId,
         amount: U128,
     ) -> U128 {
-        assert_self();
         let amount: Balance = amount.into();
 
         // Get the unused amount from the `ft_on_transfer` call result.
+        // A failed call or an invalid value means the receiver didn't take any of the tokens.
         let unused_amount = match env::promise_result(0) {
             PromiseResult::NotReady => unreachable!(),
             PromiseResult::Successful(value) => {
@@ -171,35 +175,41 @@ impl FungibleTokenResolver for Contract {
                     amount
                 }
             }
-            PromiseResult::Failed => amount, // TODO why
+            PromiseResult::Failed => amount,
         };
 
-        if unused_amount > 0 {
-            let receiver_balance = self.accounts.get(&receiver_id).unwrap_or(0);
-            if receiver_balance > 0 {
-                let refund_amount = std::cmp::min(receiver_balance, unused_amount);
-                self.accounts
-                    .insert(&receiver_id, &(receiver_balance - refund_amount));
-
-                if let Some(sender_balance) = self.accounts.get(&sender_id) {
-                    self.accounts
-                        .insert(&sender_id, &(sender_balance + refund_amount));
-                    env::log(
-                        format!(
-                            "Refund {} from {} to {}",
-                            refund_amount, receiver_id, sender_id
-                        )
-                        .as_bytes(),
-                    );
-                    return (amount - refund_amount).into();
-                } else {
-                    // Sender's account was deleted, so we need to burn tokens.
-                    self.total_supply -= refund_amount;
-                    env::log(b"The account of the sender was deleted");
-                    env::log(format!("Burn {}", refund_amount).as_bytes());
-                }
-            }
+        // The receiver may have moved some of the tokens on already, only what's left can be refunded.
+        let receiver_balance = self.accounts.get(&receiver_id).unwrap_or(0);
+        let refund_amount = std::cmp::min(receiver_balance, unused_amount);
+        if refund_amount == 0 {
+            return amount.into();
+        }
+        self.internal_checkpoint(&receiver_id);
+        self.accounts
+            .insert(&receiver_id, &(receiver_balance - refund_amount));
+
+        if let Some(sender_balance) = self.accounts.get(&sender_id) {
+            self.internal_checkpoint(&sender_id);
+            self.accounts
+                .insert(&sender_id, &(sender_balance + refund_amount));
+            FtEventKind::FtTransfer(vec![FtTransferLog {
+                old_owner_id: receiver_id,
+                new_owner_id: sender_id,
+                amount: refund_amount.into(),
+                memo: Some("refund".to_string()),
+            }])
+            .emit();
+            (amount - refund_amount).into()
+        } else {
+            // Sender's account was deleted, so we need to burn tokens.
+            self.total_supply -= refund_amount;
+            FtEventKind::FtBurn(vec![FtBurnLog {
+                owner_id: receiver_id,
+                amount: refund_amount.into(),
+                memo: Some("Refund target deleted".to_string()),
+            }])
+            .emit();
+            amount.into()
         }
-        amount.into() // TODO: i think this should be something else, how many were returned
     }
 }
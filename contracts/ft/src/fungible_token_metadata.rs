@@ -1,5 +1,6 @@
 use super::*;
-use near_sdk::serde::Serialize;
+use near_sdk::serde::{Deserialize, Serialize};
+use std::convert::TryInto;
 
 #[derive(Serialize, BorshDeserialize, BorshSerialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
@@ -12,6 +13,36 @@ pub struct FungibleTokenMetadata {
     pub decimals: u8,
 }
 
+/// `new` args, `reference_hash` is hex encoded
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FungibleTokenMetadataArgs {
+    pub version: String,
+    pub name: String,
+    pub symbol: String,
+    pub reference: String,
+    pub reference_hash: String,
+    pub decimals: u8,
+}
+
+impl From<FungibleTokenMetadataArgs> for FungibleTokenMetadata {
+    fn from(args: FungibleTokenMetadataArgs) -> Self {
+        let reference_hash = &args.reference_hash;
+        let ref_hash_bytes: Vec<u8> = (0..reference_hash.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&reference_hash[i..i + 2], 16).expect("Invalid reference_hash"))
+            .collect();
+        Self {
+            version: args.version,
+            name: args.name,
+            symbol: args.symbol,
+            reference: args.reference,
+            reference_hash: ref_hash_bytes.as_slice().try_into().expect("reference_hash must be 32 bytes"),
+            decimals: args.decimals,
+        }
+    }
+}
+
 pub trait FungibleTokenMetadataProvider {
     fn ft_metadata(&self) -> FungibleTokenMetadata;
 }
@@ -8,22 +8,35 @@ pub(crate) fn assert_one_yocto() {
     )
 }
 
-pub(crate) fn assert_self() {
-    assert_eq!(
-        env::predecessor_account_id(),
-        env::current_account_id(),
-        "Method is private"
-    );
-}
-
 impl Contract {
+    pub(crate) fn assert_owner(&self) {
+        assert!(env::predecessor_account_id() == self.owner_id, "must be owner_id");
+    }
+
+    pub(crate) fn internal_mint(&mut self, account_id: &AccountId, amount: Balance, memo: Option<String>) {
+        assert!(amount > 0, "The amount should be a positive number");
+        let total_supply = self.total_supply.checked_add(amount).expect("Total supply overflow");
+        if let Some(max_supply) = self.max_supply {
+            assert!(total_supply <= max_supply, "Minting would exceed max supply");
+        }
+        self.internal_deposit(account_id, amount);
+        self.total_supply = total_supply;
+        FtEventKind::FtMint(vec![FtMintLog {
+            owner_id: account_id.clone(),
+            amount: amount.into(),
+            memo,
+        }])
+        .emit();
+    }
+
     pub(crate) fn internal_deposit(&mut self, account_id: &AccountId, amount: Balance) {
         let balance = self
             .accounts
-            .get(&account_id)
+            .get(account_id)
             .expect("The account is not registered");
         if let Some(new_balance) = balance.checked_add(amount) {
-            self.accounts.insert(&account_id, &new_balance);
+            self.internal_checkpoint(account_id);
+            self.accounts.insert(account_id, &new_balance);
         } else {
             env::panic(b"Balance overflow");
         }
@@ -32,10 +45,15 @@ impl Contract {
     pub(crate) fn internal_withdraw(&mut self, account_id: &AccountId, amount: Balance) {
         let balance = self
             .accounts
-            .get(&account_id)
+            .get(account_id)
             .expect("The account is not registered");
         if let Some(new_balance) = balance.checked_sub(amount) {
-            self.accounts.insert(&account_id, &new_balance);
+            // CUSTOM - tokens under a vesting schedule stay until claimed
+            if new_balance < self.internal_locked_balance(account_id) {
+                env::panic(b"The account doesn't have enough unlocked balance");
+            }
+            self.internal_checkpoint(account_id);
+            self.accounts.insert(account_id, &new_balance);
         } else {
             env::panic(b"The account doesn't have enough balance");
         }
@@ -54,9 +72,12 @@ impl Contract {
         );
         self.internal_withdraw(sender_id, amount);
         self.internal_deposit(receiver_id, amount);
-        env::log(format!("Transfer {} from {} to {}", amount, sender_id, receiver_id).as_bytes());
-        if let Some(memo) = memo {
-            env::log(format!("Memo: {}", memo).as_bytes());
-        }
+        FtEventKind::FtTransfer(vec![FtTransferLog {
+            old_owner_id: sender_id.clone(),
+            new_owner_id: receiver_id.clone(),
+            amount: amount.into(),
+            memo,
+        }])
+        .emit();
     }
 }
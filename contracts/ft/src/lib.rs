@@ -9,26 +9,37 @@
 * storage_manager.rs implements NEP-145 standard for allocating storage per account
 * fungible_token_metadata.rs implements NEP-148 standard for providing token-specific metadata.
 * internal.rs contains internal methods for fungible token.
+* events.rs contains NEP-297 events logged by the token.
+* allowances.rs lets approved spenders move tokens with ft_transfer_from.
+* vesting.rs locks tokens under vesting schedules until they are claimed.
+* snapshots.rs keeps balances and total supply as of owner-triggered snapshots.
+* migrate.rs upgrades the state of contracts deployed with an older layout.
 */
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::LookupMap;
+use near_sdk::collections::{LookupMap, UnorderedSet, Vector};
 use near_sdk::json_types::{U128, ValidAccountId};
 use near_sdk::{env, near_bindgen, AccountId, Balance, Promise, StorageUsage};
 
+pub use crate::events::*;
 pub use crate::fungible_token_core::*;
 pub use crate::fungible_token_metadata::*;
 use crate::internal::*;
+pub use crate::migrate::*;
+pub use crate::snapshots::*;
 pub use crate::storage_manager::*;
-use std::num::ParseIntError;
-use std::convert::TryInto;
+pub use crate::vesting::*;
 
+mod allowances;
+mod events;
 mod fungible_token_core;
 mod fungible_token_metadata;
 mod internal;
+mod migrate;
+mod snapshots;
 mod storage_manager;
+mod vesting;
 
-#[global_allocator]
-static ALLOC: near_sdk::wee_alloc::WeeAlloc<'_> = near_sdk::wee_alloc::WeeAlloc::INIT;
+near_sdk::setup_alloc!();
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
@@ -44,7 +55,34 @@ pub struct Contract {
     /// The storage size in bytes for one account.
     pub account_storage_usage: StorageUsage,
 
-    pub ft_metadata: FungibleTokenMetadata
+    pub ft_metadata: FungibleTokenMetadata,
+
+    /// Accounts besides the owner that can call ft_mint.
+    pub minters: UnorderedSet<AccountId>,
+
+    /// Cap on total supply, set once in new.
+    pub max_supply: Option<Balance>,
+
+    /// "owner_id:spender_id" -> amount the spender can still transfer.
+    pub allowances: LookupMap<String, Balance>,
+
+    /// Owner -> spenders it gave an allowance, removed with the owner's account.
+    pub spenders_by_owner: LookupMap<AccountId, UnorderedSet<AccountId>>,
+
+    /// The storage size in bytes for one allowance.
+    pub allowance_storage_usage: StorageUsage,
+
+    /// AccountID -> vesting schedule, unclaimed tokens can't leave the account.
+    pub vesting_schedules: LookupMap<AccountId, VestingSchedule>,
+
+    /// Latest snapshot id, 0 before the first ft_snapshot.
+    pub current_snapshot_id: u64,
+
+    /// AccountID -> balances at snapshots taken before each of its balance changes.
+    pub account_checkpoints: LookupMap<AccountId, Vector<Checkpoint>>,
+
+    /// Total supply at each snapshot, snapshot id - 1 is the index.
+    pub total_supply_snapshots: Vector<Balance>,
 }
 
 impl Default for Contract {
@@ -56,27 +94,27 @@ impl Default for Contract {
 #[near_bindgen]
 impl Contract {
     #[init]
-    pub fn new(owner_id: ValidAccountId, total_supply: U128, version: String, name: String, symbol: String, reference: String, reference_hash: String, decimals: u8) -> Self {
+    pub fn new(owner_id: ValidAccountId, total_supply: U128, metadata: FungibleTokenMetadataArgs, max_supply: Option<U128>) -> Self {
         assert!(!env::state_exists(), "Already initialized");
-        let ref_hash_result: Result<Vec<u8>, ParseIntError> = (0..reference_hash.len())
-            .step_by(2)
-            .map(|i| u8::from_str_radix(&reference_hash[i..i + 2], 16))
-            .collect();
-        let ref_hash_fixed_bytes: [u8; 32] = ref_hash_result.unwrap().as_slice().try_into().unwrap();
+        if let Some(max_supply) = max_supply {
+            assert!(total_supply.0 <= max_supply.0, "Total supply exceeds max supply");
+        }
 
         let mut this = Self {
             owner_id: owner_id.clone().into(),
             accounts: LookupMap::new(b"a".to_vec()),
             total_supply: total_supply.into(),
             account_storage_usage: 0,
-            ft_metadata: FungibleTokenMetadata {
-                version,
-                name,
-                symbol,
-                reference,
-                reference_hash: ref_hash_fixed_bytes,
-                decimals
-            }
+            ft_metadata: metadata.into(),
+            minters: UnorderedSet::new(b"m".to_vec()),
+            max_supply: max_supply.map(|m| m.into()),
+            allowances: LookupMap::new(b"l".to_vec()),
+            spenders_by_owner: LookupMap::new(b"p".to_vec()),
+            allowance_storage_usage: 0,
+            vesting_schedules: LookupMap::new(b"v".to_vec()),
+            current_snapshot_id: 0,
+            account_checkpoints: LookupMap::new(b"c".to_vec()),
+            total_supply_snapshots: Vector::new(b"s".to_vec()),
         };
         // Determine cost of insertion into LookupMap
         let initial_storage_usage = env::storage_usage();
@@ -84,21 +122,85 @@ impl Contract {
         this.accounts.insert(&tmp_account_id, &0u128);
         this.account_storage_usage = env::storage_usage() - initial_storage_usage;
         this.accounts.remove(&tmp_account_id);
+        this.measure_allowance_storage_usage();
         // Make owner have total supply
         let total_supply_u128: u128 = total_supply.into();
-        this.accounts.insert(&owner_id.as_ref(), &total_supply_u128);
+        this.accounts.insert(owner_id.as_ref(), &total_supply_u128);
+        FtEventKind::FtMint(vec![FtMintLog {
+            owner_id: owner_id.into(),
+            amount: total_supply,
+            memo: Some("Initial token supply".to_string()),
+        }])
+        .emit();
+        write_contract_version();
         this
     }
 
+    /// Determine cost of an allowance between two max length accounts, with a new spender set for the owner
+    fn measure_allowance_storage_usage(&mut self) {
+        let initial_storage_usage = env::storage_usage();
+        let tmp_account_id = unsafe { String::from_utf8_unchecked(vec![b'a'; 64]) };
+        self.internal_insert_allowance(&tmp_account_id, &tmp_account_id, 0);
+        self.allowance_storage_usage = env::storage_usage() - initial_storage_usage;
+        self.internal_remove_allowances(&tmp_account_id);
+    }
+
     /// Custom Methods
 
-    /// only owner can mint
+    /// owner or minters, account_id must be registered
+    pub fn ft_mint(&mut self, account_id: ValidAccountId, amount: U128, memo: Option<String>) {
+        let predecessor_id = env::predecessor_account_id();
+        assert!(
+            predecessor_id == self.owner_id || self.minters.contains(&predecessor_id),
+            "must be owner_id or minter"
+        );
+        self.internal_mint(account_id.as_ref(), amount.into(), memo);
+    }
+
+    /// holders burn their own tokens
+    #[payable]
+    pub fn ft_burn(&mut self, amount: U128, memo: Option<String>) {
+        assert_one_yocto();
+        let amount: Balance = amount.into();
+        assert!(amount > 0, "The amount should be a positive number");
+        let account_id = env::predecessor_account_id();
+        self.internal_withdraw(&account_id, amount);
+        self.total_supply -= amount;
+        FtEventKind::FtBurn(vec![FtBurnLog {
+            owner_id: account_id,
+            amount: amount.into(),
+            memo,
+        }])
+        .emit();
+    }
+
+    /// only owner
+    pub fn add_minter(&mut self, account_id: ValidAccountId) -> bool {
+        self.assert_owner();
+        self.minters.insert(account_id.as_ref())
+    }
+
+    /// only owner
+    pub fn remove_minter(&mut self, account_id: ValidAccountId) -> bool {
+        self.assert_owner();
+        self.minters.remove(account_id.as_ref())
+    }
+
+    /// deprecated, use ft_mint
     pub fn mint(&mut self, amount: U128) {
-        assert!(env::predecessor_account_id() == self.owner_id, "must be owner_id");
-        self.total_supply += u128::from(amount);
-        let mut balance = self.accounts.get(&self.owner_id).expect("owner should have balance");
-        balance += u128::from(amount);
-        self.accounts.insert(&self.owner_id, &balance);
+        self.assert_owner();
+        let owner_id = self.owner_id.clone();
+        self.internal_mint(&owner_id, amount.into(), None);
+    }
+
+    /// views
+
+    pub fn ft_minters(&self) -> Vec<AccountId> {
+        self.minters.to_vec()
+    }
+
+    pub fn ft_max_supply(&self) -> Option<U128> {
+        self.max_supply.map(|m| m.into())
     }
 }
 
@@ -106,7 +208,8 @@ impl Contract {
 #[cfg(test)]
 mod fungible_token_tests {
     use near_sdk::MockedBlockchain;
-    use near_sdk::{testing_env, VMContext};
+    use near_sdk::test_utils::testing_env_with_promise_results;
+    use near_sdk::{testing_env, PromiseResult, VMContext};
 
     use super::*;
     use near_sdk::json_types::ValidAccountId;
@@ -155,19 +258,13 @@ mod fungible_token_tests {
         let contract = Contract::new(
             dex(),
             U128::from(1_000_000_000_000_000),
-            String::from("0.1.0"),
-            String::from("NEAR Test Token"),
-            String::from("TEST"),
-            String::from(
-                "https://github.com/near/core-contracts/tree/master/w-near-141",
-            ),
-            "7c879fa7b49901d0ecc6ff5d64d7f673da5e4a5eb52a8d50a214175760d8919a".to_string(),
-            24
+            sample_metadata(),
+            None
         );
         assert_eq!(contract.ft_total_supply().0, 1_000_000_000_000_000);
         assert_eq!(contract.ft_balance_of(alice()).0, ZERO_U128);
-        assert_eq!(contract.ft_balance_of(bob().into()).0, ZERO_U128);
-        assert_eq!(contract.ft_balance_of(carol().into()).0, ZERO_U128);
+        assert_eq!(contract.ft_balance_of(bob()).0, ZERO_U128);
+        assert_eq!(contract.ft_balance_of(carol()).0, ZERO_U128);
     }
 
     #[test]
@@ -176,4 +273,503 @@ mod fungible_token_tests {
         testing_env!(get_context(carol().into()));
         let _contract = Contract::default();
     }
+
+    fn sample_metadata() -> FungibleTokenMetadataArgs {
+        FungibleTokenMetadataArgs {
+            version: String::from("0.1.0"),
+            name: String::from("NEAR Test Token"),
+            symbol: String::from("TEST"),
+            reference: String::from("https://github.com/near/core-contracts/tree/master/w-near-141"),
+            reference_hash: "7c879fa7b49901d0ecc6ff5d64d7f673da5e4a5eb52a8d50a214175760d8919a".to_string(),
+            decimals: 24,
+        }
+    }
+
+    fn setup_contract() -> Contract {
+        testing_env!(get_context(dex().into()));
+        Contract::new(
+            dex(),
+            U128::from(1_000_000_000_000_000),
+            sample_metadata(),
+            None
+        )
+    }
+
+    fn register(contract: &mut Contract, account_id: ValidAccountId) {
+        let mut context = get_context(account_id.clone().into());
+        context.attached_deposit = contract.storage_minimum_balance().0 * 2;
+        testing_env!(context);
+        contract.storage_deposit(Some(account_id), None);
+    }
+
+    #[test]
+    fn storage_deposit_is_idempotent() {
+        let mut contract = setup_contract();
+        assert!(contract.storage_balance_of(alice()).is_none());
+        register(&mut contract, alice());
+        register(&mut contract, alice());
+        let balance = contract.storage_balance_of(alice()).unwrap();
+        assert_eq!(balance.total.0, contract.storage_minimum_balance().0);
+        assert_eq!(balance.available.0, ZERO_U128);
+    }
+
+    #[test]
+    fn mint_logs_nep297_event() {
+        let mut contract = setup_contract();
+        register(&mut contract, alice());
+        testing_env!(get_context(dex().into()));
+        contract.ft_mint(alice(), U128::from(10), Some("airdrop".to_string()));
+        assert_eq!(
+            near_sdk::test_utils::get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"nep141","version":"1.0.0","event":"ft_mint","data":[{"owner_id":"alice.near","amount":"10","memo":"airdrop"}]}"#]
+        );
+    }
+
+    #[test]
+    fn storage_deposit_registration_only_refunds_above_minimum() {
+        let mut contract = setup_contract();
+        let min_balance = contract.storage_minimum_balance().0;
+        let mut context = get_context(bob().into());
+        context.attached_deposit = min_balance * 3;
+        testing_env!(context);
+        let balance = contract.storage_deposit(Some(alice()), Some(true));
+        assert_eq!(balance.total.0, min_balance);
+        let receipts = near_sdk::serde_json::to_string(&near_sdk::test_utils::get_created_receipts()).unwrap();
+        assert!(receipts.contains(&format!("{{\"Transfer\":{{\"deposit\":{}}}}}", min_balance * 2)));
+        assert_eq!(contract.storage_balance_bounds().max.unwrap().0, min_balance);
+    }
+
+    #[test]
+    #[should_panic(expected = "The amount is greater than the available storage balance")]
+    fn storage_withdraw_is_always_zero() {
+        let mut contract = setup_contract();
+        register(&mut contract, alice());
+        let mut context = get_context(alice().into());
+        context.attached_deposit = 1;
+        testing_env!(context);
+        assert_eq!(contract.storage_withdraw(None).available.0, ZERO_U128);
+        contract.storage_withdraw(Some(U128::from(1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't unregister the account with the positive balance without force")]
+    fn storage_unregister_with_balance_requires_force() {
+        let mut contract = setup_contract();
+        register(&mut contract, alice());
+        let mut context = get_context(dex().into());
+        context.attached_deposit = 1;
+        testing_env!(context.clone());
+        contract.ft_transfer(alice(), U128::from(100), None);
+
+        context.predecessor_account_id = alice().into();
+        testing_env!(context);
+        contract.storage_unregister(None);
+    }
+
+    #[test]
+    fn storage_unregister_force_burns_balance() {
+        let mut contract = setup_contract();
+        register(&mut contract, alice());
+        let mut context = get_context(dex().into());
+        context.attached_deposit = 1;
+        testing_env!(context.clone());
+        contract.ft_transfer(alice(), U128::from(100), None);
+
+        context.predecessor_account_id = alice().into();
+        testing_env!(context);
+        assert!(contract.storage_unregister(Some(true)));
+        assert!(contract.storage_balance_of(alice()).is_none());
+        assert_eq!(contract.ft_total_supply().0, 1_000_000_000_000_000 - 100);
+        assert!(!contract.storage_unregister(None));
+    }
+
+    /// dex sent 100 to alice with ft_transfer_call, resolve with the result of alice's ft_on_transfer
+    fn resolve_transfer(contract: &mut Contract, sender_id: ValidAccountId, promise_result: PromiseResult) -> Balance {
+        testing_env_with_promise_results(get_context("mike.near".to_string()), promise_result);
+        contract
+            .ft_resolve_transfer(sender_id.into(), alice().into(), U128::from(100))
+            .0
+    }
+
+    fn setup_transfer_call(sender_id: ValidAccountId) -> Contract {
+        let mut contract = setup_contract();
+        register(&mut contract, alice());
+        if sender_id.as_ref() != dex().as_ref() {
+            register(&mut contract, sender_id.clone());
+            contract.internal_transfer(dex().as_ref(), sender_id.as_ref(), 100, None);
+        }
+        contract.internal_transfer(sender_id.as_ref(), alice().as_ref(), 100, None);
+        contract
+    }
+
+    #[test]
+    fn resolve_transfer_all_used() {
+        let mut contract = setup_transfer_call(dex());
+        let used = resolve_transfer(&mut contract, dex(), PromiseResult::Successful(b"\"0\"".to_vec()));
+        assert_eq!(used, 100);
+        assert_eq!(contract.ft_balance_of(alice()).0, 100);
+    }
+
+    #[test]
+    fn resolve_transfer_partial_refund() {
+        let mut contract = setup_transfer_call(dex());
+        let used = resolve_transfer(&mut contract, dex(), PromiseResult::Successful(b"\"40\"".to_vec()));
+        assert_eq!(used, 60);
+        assert_eq!(contract.ft_balance_of(alice()).0, 60);
+        assert_eq!(contract.ft_balance_of(dex()).0, 1_000_000_000_000_000 - 60);
+    }
+
+    #[test]
+    fn resolve_transfer_unused_capped_at_amount() {
+        let mut contract = setup_transfer_call(dex());
+        let used = resolve_transfer(&mut contract, dex(), PromiseResult::Successful(b"\"500\"".to_vec()));
+        assert_eq!(used, 0);
+        assert_eq!(contract.ft_balance_of(alice()).0, 0);
+    }
+
+    #[test]
+    fn resolve_transfer_failed_promise_refunds_all() {
+        let mut contract = setup_transfer_call(dex());
+        let used = resolve_transfer(&mut contract, dex(), PromiseResult::Failed);
+        assert_eq!(used, 0);
+        assert_eq!(contract.ft_balance_of(alice()).0, 0);
+        assert_eq!(contract.ft_balance_of(dex()).0, 1_000_000_000_000_000);
+    }
+
+    #[test]
+    fn resolve_transfer_receiver_spent_tokens() {
+        let mut contract = setup_transfer_call(dex());
+        register(&mut contract, bob());
+        contract.internal_transfer(alice().as_ref(), bob().as_ref(), 70, None);
+        let used = resolve_transfer(&mut contract, dex(), PromiseResult::Failed);
+        assert_eq!(used, 70);
+        assert_eq!(contract.ft_balance_of(alice()).0, 0);
+        assert_eq!(contract.ft_balance_of(bob()).0, 70);
+    }
+
+    #[test]
+    fn resolve_transfer_deleted_sender_burns_refund() {
+        let mut contract = setup_transfer_call(carol());
+        contract.accounts.remove(carol().as_ref());
+        let used = resolve_transfer(&mut contract, carol(), PromiseResult::Failed);
+        assert_eq!(used, 100);
+        assert_eq!(contract.ft_balance_of(alice()).0, 0);
+        assert_eq!(contract.ft_total_supply().0, 1_000_000_000_000_000 - 100);
+    }
+
+    #[test]
+    fn minter_mints_to_registered_account() {
+        let mut contract = setup_contract();
+        register(&mut contract, alice());
+        testing_env!(get_context(dex().into()));
+        contract.add_minter(bob());
+        testing_env!(get_context(bob().into()));
+        contract.ft_mint(alice(), U128::from(50), Some("airdrop".to_string()));
+        assert_eq!(contract.ft_balance_of(alice()).0, 50);
+        assert_eq!(contract.ft_total_supply().0, 1_000_000_000_000_000 + 50);
+    }
+
+    #[test]
+    #[should_panic(expected = "must be owner_id or minter")]
+    fn stranger_cannot_mint() {
+        let mut contract = setup_contract();
+        register(&mut contract, alice());
+        testing_env!(get_context(alice().into()));
+        contract.ft_mint(alice(), U128::from(50), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Minting would exceed max supply")]
+    fn ft_mint_respects_max_supply() {
+        let mut contract = setup_contract();
+        contract.max_supply = Some(1_000_000_000_000_000 + 10);
+        testing_env!(get_context(dex().into()));
+        contract.ft_mint(dex(), U128::from(11), None);
+    }
+
+    #[test]
+    fn ft_burn_reduces_total_supply() {
+        let mut contract = setup_contract();
+        let mut context = get_context(dex().into());
+        context.attached_deposit = 1;
+        testing_env!(context);
+        contract.ft_burn(U128::from(100), None);
+        assert_eq!(contract.ft_balance_of(dex()).0, 1_000_000_000_000_000 - 100);
+        assert_eq!(contract.ft_total_supply().0, 1_000_000_000_000_000 - 100);
+    }
+
+    fn approve(contract: &mut Contract, owner_id: ValidAccountId, spender_id: ValidAccountId, amount: Balance) {
+        let mut context = get_context(owner_id.into());
+        context.attached_deposit = contract.ft_allowance_storage_cost().0;
+        testing_env!(context);
+        contract.ft_approve(spender_id, U128::from(amount));
+    }
+
+    #[test]
+    fn transfer_from_decrements_allowance() {
+        let mut contract = setup_contract();
+        register(&mut contract, alice());
+        approve(&mut contract, dex(), bob(), 100);
+        let mut context = get_context(bob().into());
+        context.attached_deposit = 1;
+        testing_env!(context);
+        contract.ft_transfer_from(dex(), alice(), U128::from(60), None);
+        assert_eq!(contract.ft_balance_of(alice()).0, 60);
+        assert_eq!(contract.ft_allowance(dex(), bob()).0, 40);
+    }
+
+    #[test]
+    #[should_panic(expected = "The amount exceeds the allowance")]
+    fn transfer_from_over_allowance() {
+        let mut contract = setup_contract();
+        register(&mut contract, alice());
+        approve(&mut contract, dex(), bob(), 100);
+        let mut context = get_context(bob().into());
+        context.attached_deposit = 1;
+        testing_env!(context);
+        contract.ft_transfer_from(dex(), alice(), U128::from(101), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "No allowance for this spender")]
+    fn revoked_allowance_cannot_be_used() {
+        let mut contract = setup_contract();
+        register(&mut contract, alice());
+        approve(&mut contract, dex(), bob(), 100);
+        let mut context = get_context(dex().into());
+        context.attached_deposit = 1;
+        testing_env!(context.clone());
+        contract.ft_revoke(bob());
+        assert_eq!(contract.ft_allowance(dex(), bob()).0, 0);
+
+        context.predecessor_account_id = bob().into();
+        testing_env!(context);
+        contract.ft_transfer_from(dex(), alice(), U128::from(1), None);
+    }
+
+    #[test]
+    fn storage_unregister_removes_allowances_and_refunds_them() {
+        let mut contract = setup_contract();
+        register(&mut contract, alice());
+        approve(&mut contract, alice(), bob(), 100);
+        approve(&mut contract, alice(), carol(), 100);
+        approve(&mut contract, dex(), bob(), 100);
+
+        let mut context = get_context(alice().into());
+        context.attached_deposit = 1;
+        testing_env!(context);
+        assert!(contract.storage_unregister(None));
+        let receipts = near_sdk::serde_json::to_string(&near_sdk::test_utils::get_created_receipts()).unwrap();
+        let refund = contract.storage_minimum_balance().0 + 2 * contract.ft_allowance_storage_cost().0;
+        assert!(receipts.contains(&format!("{{\"Transfer\":{{\"deposit\":{}}}}}", refund)));
+        assert_eq!(contract.ft_allowance(alice(), bob()).0, 0);
+        assert_eq!(contract.ft_allowance(alice(), carol()).0, 0);
+        assert!(contract.spenders_by_owner.get(alice().as_ref()).is_none());
+        assert_eq!(contract.ft_allowance(dex(), bob()).0, 100);
+    }
+
+    /// alice vests 1000 linearly over the first second, cliff at 100ms
+    fn setup_vesting() -> Contract {
+        let mut contract = setup_contract();
+        register(&mut contract, alice());
+        let mut context = get_context(dex().into());
+        context.attached_deposit = 10u128.pow(24);
+        testing_env!(context);
+        contract.create_vesting_schedule(
+            alice(),
+            VestingScheduleArgs {
+                total: U128::from(1000),
+                start: 0.into(),
+                cliff: 100.into(),
+                end: 1000.into(),
+                steps: VestingSteps::Linear,
+                revocable: true,
+            },
+        );
+        contract
+    }
+
+    fn alice_at(ms: u64, attached_deposit: Balance) {
+        let mut context = get_context(alice().into());
+        context.block_timestamp = ms * 1_000_000;
+        context.attached_deposit = attached_deposit;
+        testing_env!(context);
+    }
+
+    #[test]
+    fn claim_vested_unlocks_vested_tokens() {
+        let mut contract = setup_vesting();
+        alice_at(500, 0);
+        assert_eq!(contract.ft_vesting_balance(alice()).vested.0, 500);
+        assert_eq!(contract.claim_vested().0, 500);
+        alice_at(500, 1);
+        contract.ft_transfer(dex(), U128::from(500), None);
+        assert_eq!(contract.ft_balance_of(alice()).0, 500);
+        assert_eq!(contract.ft_vesting_balance(alice()).locked.0, 500);
+    }
+
+    #[test]
+    #[should_panic(expected = "The account doesn't have enough unlocked balance")]
+    fn locked_tokens_cannot_be_transferred() {
+        let mut contract = setup_vesting();
+        alice_at(500, 0);
+        contract.claim_vested();
+        alice_at(500, 1);
+        contract.ft_transfer(dex(), U128::from(501), None);
+    }
+
+    #[test]
+    fn revoke_returns_unvested_tokens() {
+        let mut contract = setup_vesting();
+        let mut context = get_context(dex().into());
+        context.block_timestamp = 250 * 1_000_000;
+        testing_env!(context);
+        assert_eq!(contract.revoke_vesting_schedule(alice()).0, 750);
+        assert_eq!(contract.ft_balance_of(alice()).0, 250);
+        alice_at(2000, 0);
+        assert_eq!(contract.claim_vested().0, 250);
+    }
+
+    #[test]
+    fn fully_claimed_schedule_is_removed_and_its_storage_refunded() {
+        let mut contract = setup_vesting();
+        alice_at(500, 0);
+        contract.claim_vested();
+        alice_at(1000, 0);
+        assert_eq!(contract.claim_vested().0, 500);
+        assert!(contract.ft_vesting_schedule(alice()).is_none());
+        let receipts = near_sdk::serde_json::to_string(&near_sdk::test_utils::get_created_receipts()).unwrap();
+        assert!(receipts.contains(&format!("\"receiver_id\":\"{}\"", dex())));
+        assert!(receipts.contains("{\"Transfer\":{\"deposit\":"));
+
+        // the account can leave now
+        alice_at(1000, 1);
+        assert!(contract.storage_unregister(Some(true)));
+    }
+
+    #[test]
+    fn revoke_removes_schedule_without_vested_tokens() {
+        let mut contract = setup_vesting();
+        let mut context = get_context(dex().into());
+        context.block_timestamp = 50 * 1_000_000;
+        testing_env!(context);
+        assert_eq!(contract.revoke_vesting_schedule(alice()).0, 1000);
+        assert!(contract.ft_vesting_schedule(alice()).is_none());
+        let receipts = near_sdk::serde_json::to_string(&near_sdk::test_utils::get_created_receipts()).unwrap();
+        assert!(receipts.contains("{\"Transfer\":{\"deposit\":"));
+    }
+
+    #[test]
+    fn monthly_steps_vest_per_full_month() {
+        let schedule = VestingSchedule {
+            total: U128::from(1200),
+            claimed: U128::from(0),
+            start: 0.into(),
+            cliff: 0.into(),
+            end: (12 * VESTING_MONTH).into(),
+            steps: VestingSteps::Monthly,
+            revocable: false,
+        };
+        assert_eq!(schedule.vested_at(VESTING_MONTH - 1), 0);
+        assert_eq!(schedule.vested_at(VESTING_MONTH), 100);
+        assert_eq!(schedule.vested_at(5 * VESTING_MONTH + 10), 500);
+        assert_eq!(schedule.vested_at(12 * VESTING_MONTH), 1200);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't unregister the account with a vesting schedule")]
+    fn storage_unregister_with_vesting_schedule() {
+        let mut contract = setup_vesting();
+        alice_at(0, 1);
+        contract.storage_unregister(Some(true));
+    }
+
+    #[test]
+    fn storage_unregister_keeps_past_snapshots() {
+        let mut contract = setup_contract();
+        register(&mut contract, alice());
+        testing_env!(get_context(dex().into()));
+        contract.internal_transfer(dex().as_ref(), alice().as_ref(), 100, None);
+        contract.ft_snapshot();
+        contract.internal_transfer(dex().as_ref(), alice().as_ref(), 50, None);
+        contract.ft_snapshot();
+
+        let mut context = get_context(alice().into());
+        context.attached_deposit = 1;
+        testing_env!(context);
+        assert!(contract.storage_unregister(Some(true)));
+        assert_eq!(contract.ft_balance_of_at(alice(), 1.into()).0, 100);
+        assert_eq!(contract.ft_balance_of_at(alice(), 2.into()).0, 150);
+        assert_eq!(contract.ft_balance_of(alice()).0, 0);
+        assert_eq!(contract.ft_total_supply_at(2.into()).0, 1_000_000_000_000_000);
+        assert_eq!(contract.ft_total_supply().0, 1_000_000_000_000_000 - 150);
+    }
+
+    #[test]
+    fn snapshot_keeps_balances_before_changes() {
+        let mut contract = setup_contract();
+        register(&mut contract, alice());
+        testing_env!(get_context(dex().into()));
+        contract.internal_transfer(dex().as_ref(), alice().as_ref(), 100, None);
+        assert_eq!(contract.ft_snapshot().0, 1);
+
+        contract.internal_transfer(dex().as_ref(), alice().as_ref(), 50, None);
+        let mut context = get_context(dex().into());
+        context.attached_deposit = 1;
+        testing_env!(context);
+        contract.ft_burn(U128::from(1000), None);
+        testing_env!(get_context(dex().into()));
+        assert_eq!(contract.ft_snapshot().0, 2);
+        contract.internal_transfer(alice().as_ref(), dex().as_ref(), 30, None);
+
+        assert_eq!(contract.ft_balance_of_at(alice(), 1.into()).0, 100);
+        assert_eq!(contract.ft_balance_of_at(alice(), 2.into()).0, 150);
+        assert_eq!(contract.ft_balance_of(alice()).0, 120);
+        assert_eq!(contract.ft_balance_of_at(bob(), 1.into()).0, 0);
+        assert_eq!(contract.ft_total_supply_at(1.into()).0, 1_000_000_000_000_000);
+        assert_eq!(contract.ft_total_supply_at(2.into()).0, 1_000_000_000_000_000 - 1000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid snapshot id")]
+    fn balance_of_at_future_snapshot() {
+        let contract = setup_contract();
+        contract.ft_balance_of_at(alice(), 1.into());
+    }
+
+    fn write_v1_state() {
+        testing_env!(get_context(dex().into()));
+        let mut accounts = LookupMap::new(b"a".to_vec());
+        accounts.insert(&dex().into(), &900u128);
+        accounts.insert(&alice().into(), &100u128);
+        env::state_write(&ContractV1 {
+            owner_id: dex().into(),
+            accounts,
+            total_supply: 1000,
+            account_storage_usage: 100,
+            ft_metadata: FungibleTokenMetadata::from(sample_metadata()),
+        });
+    }
+
+    #[test]
+    fn migrate_from_v1() {
+        write_v1_state();
+        let mut contract = Contract::migrate();
+        assert_eq!(contract.contract_version(), CONTRACT_VERSION);
+        assert_eq!(contract.ft_total_supply().0, 1000);
+        assert_eq!(contract.ft_balance_of(alice()).0, 100);
+        assert_eq!(contract.ft_max_supply(), None);
+        assert!(contract.ft_allowance_storage_cost().0 > 0);
+
+        contract.ft_mint(alice(), U128::from(50), None);
+        assert_eq!(contract.ft_balance_of(alice()).0, 150);
+    }
+
+    #[test]
+    #[should_panic(expected = "Owner's method")]
+    fn migrate_requires_owner() {
+        write_v1_state();
+        testing_env!(get_context(alice().into()));
+        Contract::migrate();
+    }
 }
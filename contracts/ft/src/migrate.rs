@@ -0,0 +1,72 @@
+use crate::*;
+
+/// Version of the `Contract` layout written by this code.
+/// State written before the version key existed is version 1.
+pub const CONTRACT_VERSION: u32 = 2;
+const STATE_VERSION_KEY: &[u8] = b"VERSION";
+
+/// Layout of version 1, before minters, max supply, allowances, vesting and snapshots.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractV1 {
+    pub owner_id: AccountId,
+    pub accounts: LookupMap<AccountId, Balance>,
+    pub total_supply: Balance,
+    pub account_storage_usage: StorageUsage,
+    pub ft_metadata: FungibleTokenMetadata,
+}
+
+pub(crate) fn stored_contract_version() -> u32 {
+    env::storage_read(STATE_VERSION_KEY)
+        .map(|version| u32::try_from_slice(&version).expect("Invalid contract version"))
+        .unwrap_or(1)
+}
+
+pub(crate) fn write_contract_version() {
+    env::storage_write(STATE_VERSION_KEY, &CONTRACT_VERSION.try_to_vec().unwrap());
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Upgrades the stored state after new code is deployed, called by the owner or the contract account.
+    /// Balances, total supply and metadata are kept. Version 1 state gets empty minters, allowances,
+    /// vesting schedules and snapshots, and no max supply.
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let this = match stored_contract_version() {
+            1 => {
+                let old: ContractV1 = env::state_read().expect("No contract state");
+                let mut this = Self {
+                    owner_id: old.owner_id,
+                    accounts: old.accounts,
+                    total_supply: old.total_supply,
+                    account_storage_usage: old.account_storage_usage,
+                    ft_metadata: old.ft_metadata,
+                    minters: UnorderedSet::new(b"m".to_vec()),
+                    max_supply: None,
+                    allowances: LookupMap::new(b"l".to_vec()),
+                    spenders_by_owner: LookupMap::new(b"p".to_vec()),
+                    allowance_storage_usage: 0,
+                    vesting_schedules: LookupMap::new(b"v".to_vec()),
+                    current_snapshot_id: 0,
+                    account_checkpoints: LookupMap::new(b"c".to_vec()),
+                    total_supply_snapshots: Vector::new(b"s".to_vec()),
+                };
+                this.measure_allowance_storage_usage();
+                this
+            }
+            CONTRACT_VERSION => env::state_read().expect("No contract state"),
+            version => env::panic(format!("Unknown contract version {}", version).as_bytes()),
+        };
+        let predecessor_account_id = env::predecessor_account_id();
+        assert!(
+            predecessor_account_id == this.owner_id || predecessor_account_id == env::current_account_id(),
+            "Owner's method"
+        );
+        write_contract_version();
+        this
+    }
+
+    pub fn contract_version(&self) -> u32 {
+        stored_contract_version()
+    }
+}
@@ -0,0 +1,95 @@
+use crate::*;
+use near_sdk::json_types::{ValidAccountId, U128, U64};
+
+/// An account's balance as it was when `snapshot_id` was taken, written lazily the first time
+/// the balance changes after that snapshot. Only accounts whose balance changed take storage,
+/// the contract pays for it so transfers never need a deposit.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Checkpoint {
+    pub snapshot_id: u64,
+    pub balance: Balance,
+}
+
+/// Prefix of the account's checkpoints.
+fn checkpoints_prefix(account_id: &AccountId) -> Vec<u8> {
+    [b"k".as_ref(), &env::sha256(account_id.as_bytes())].concat()
+}
+
+#[near_bindgen]
+impl Contract {
+    /// only owner, returns the new snapshot id (ids start at 1)
+    pub fn ft_snapshot(&mut self) -> U64 {
+        self.assert_owner();
+        self.total_supply_snapshots.push(&self.total_supply);
+        self.current_snapshot_id = self.total_supply_snapshots.len();
+        U64(self.current_snapshot_id)
+    }
+
+    /// views
+
+    pub fn ft_current_snapshot_id(&self) -> U64 {
+        U64(self.current_snapshot_id)
+    }
+
+    pub fn ft_balance_of_at(&self, account_id: ValidAccountId, snapshot_id: U64) -> U128 {
+        self.assert_valid_snapshot_id(snapshot_id.0);
+        // the first checkpoint at or after the snapshot holds the balance, none means it hasn't changed since
+        if let Some(checkpoints) = self.account_checkpoints.get(account_id.as_ref()) {
+            let (mut low, mut high) = (0, checkpoints.len());
+            while low < high {
+                let mid = (low + high) / 2;
+                if checkpoints.get(mid).unwrap().snapshot_id < snapshot_id.0 {
+                    low = mid + 1;
+                } else {
+                    high = mid;
+                }
+            }
+            if let Some(checkpoint) = checkpoints.get(low) {
+                return checkpoint.balance.into();
+            }
+        }
+        self.accounts.get(account_id.as_ref()).unwrap_or(0).into()
+    }
+
+    pub fn ft_total_supply_at(&self, snapshot_id: U64) -> U128 {
+        self.assert_valid_snapshot_id(snapshot_id.0);
+        self.total_supply_snapshots.get(snapshot_id.0 - 1).unwrap().into()
+    }
+}
+
+impl Contract {
+    fn assert_valid_snapshot_id(&self, snapshot_id: u64) {
+        assert!(
+            snapshot_id > 0 && snapshot_id <= self.current_snapshot_id,
+            "Invalid snapshot id"
+        );
+    }
+
+    /// call before changing an account's balance, keeps its balance for the latest snapshot
+    pub(crate) fn internal_checkpoint(&mut self, account_id: &AccountId) {
+        if !self.internal_needs_checkpoint(account_id) {
+            return;
+        }
+        let mut checkpoints = self
+            .account_checkpoints
+            .get(account_id)
+            .unwrap_or_else(|| Vector::new(checkpoints_prefix(account_id)));
+        checkpoints.push(&Checkpoint {
+            snapshot_id: self.current_snapshot_id,
+            balance: self.accounts.get(account_id).unwrap_or(0),
+        });
+        self.account_checkpoints.insert(account_id, &checkpoints);
+    }
+
+    fn internal_needs_checkpoint(&self, account_id: &AccountId) -> bool {
+        if self.current_snapshot_id == 0 {
+            return false;
+        }
+        let last_snapshot_id = self
+            .account_checkpoints
+            .get(account_id)
+            .and_then(|checkpoints| checkpoints.len().checked_sub(1).and_then(|last| checkpoints.get(last)))
+            .map(|c| c.snapshot_id);
+        last_snapshot_id != Some(self.current_snapshot_id)
+    }
+}
@@ -9,64 +9,133 @@ pub const STORAGE_PRICE_PER_BYTE: Balance = 10_000_000_000_000_000_000;
 #[derive(Serialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct AccountStorageBalance {
-    total: U128,
-    available: U128,
+    pub total: U128,
+    pub available: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalanceBounds {
+    pub min: U128,
+    pub max: Option<U128>,
 }
 
 pub trait StorageManager {
-    fn storage_deposit(&mut self, account_id: Option<ValidAccountId>) -> AccountStorageBalance;
+    /// Registers `account_id` (or the predecessor) and refunds anything above the minimum balance.
+    /// Registering an account again refunds the whole deposit. The minimum balance is also the maximum
+    /// (see `storage_balance_bounds`), so every deposit is handled as NEP-145 specifies for
+    /// `registration_only`, whatever it is set to.
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<ValidAccountId>,
+        registration_only: Option<bool>,
+    ) -> AccountStorageBalance;
 
-    fn storage_withdraw(&mut self, amount: U128) -> AccountStorageBalance;
+    /// Withdrawals are always 0: nothing is ever available above the minimum balance, so any positive
+    /// `amount` panics. Use `storage_unregister` to get the storage deposit back.
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> AccountStorageBalance;
+
+    /// Removes the predecessor's account with its allowances, and returns its
+    /// storage deposit and the allowances' deposits. An account with tokens panics unless `force` is set,
+    /// then its tokens are burned. Its balances at past snapshots don't change. An account with a vesting
+    /// schedule always panics. Returns false if it wasn't registered.
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool;
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds;
 
     fn storage_minimum_balance(&self) -> U128;
 
-    fn storage_balance_of(&self, account_id: ValidAccountId) -> AccountStorageBalance;
+    fn storage_balance_of(&self, account_id: ValidAccountId) -> Option<AccountStorageBalance>;
 }
 
 #[near_bindgen]
 impl StorageManager for Contract {
     #[payable]
-    fn storage_deposit(&mut self, account_id: Option<ValidAccountId>) -> AccountStorageBalance {
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<ValidAccountId>,
+        registration_only: Option<bool>,
+    ) -> AccountStorageBalance {
+        // min == max, registration_only can't change what is kept
+        let _ = registration_only;
         let amount = env::attached_deposit();
-        assert_eq!(
-            amount,
-            self.storage_minimum_balance().0,
-            "Requires attached deposit of the exact storage minimum balance"
-        );
         let account_id = account_id
             .map(|a| a.into())
-            .unwrap_or_else(|| env::predecessor_account_id());
-        if self.accounts.insert(&account_id, &0).is_some() {
-            env::panic(b"The account is already registered");
+            .unwrap_or_else(env::predecessor_account_id);
+        if self.accounts.contains_key(&account_id) {
+            env::log(b"The account is already registered, refunding the deposit");
+            if amount > 0 {
+                Promise::new(env::predecessor_account_id()).transfer(amount);
+            }
+        } else {
+            let min_balance = self.storage_minimum_balance().0;
+            assert!(
+                amount >= min_balance,
+                "The attached deposit is less than the minimum storage balance"
+            );
+            self.accounts.insert(&account_id, &0);
+            let refund = amount - min_balance;
+            if refund > 0 {
+                Promise::new(env::predecessor_account_id()).transfer(refund);
+            }
         }
-        AccountStorageBalance {
-            total: amount.into(),
-            available: amount.into(),
+        self.internal_storage_balance_of(&account_id).unwrap()
+    }
+
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> AccountStorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let storage_balance = self
+            .internal_storage_balance_of(&account_id)
+            .unwrap_or_else(|| env::panic(format!("The account {} is not registered", account_id).as_bytes()));
+        if amount.map(|a| a.0).unwrap_or(0) > 0 {
+            env::panic(b"The amount is greater than the available storage balance");
         }
+        storage_balance
     }
 
     #[payable]
-    fn storage_withdraw(&mut self, amount: U128) -> AccountStorageBalance {
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
         assert_one_yocto();
-        let amount: Balance = amount.into();
-        assert_eq!(
-            amount,
-            self.storage_minimum_balance().0,
-            "The withdrawal amount should be the exact storage minimum balance"
-        );
         let account_id = env::predecessor_account_id();
-        if let Some(balance) = self.accounts.remove(&account_id) {
-            if balance > 0 {
-                env::panic(b"The account has positive token balance");
-            } else {
-                Promise::new(account_id).transfer(amount + 1);
-                AccountStorageBalance {
-                    total: 0.into(),
-                    available: 0.into(),
-                }
-            }
+        assert_ne!(account_id, self.owner_id, "The owner can't unregister");
+        let balance = if let Some(balance) = self.accounts.get(&account_id) {
+            balance
         } else {
-            env::panic(b"The account is not registered");
+            env::log(format!("The account {} is not registered", account_id).as_bytes());
+            return false;
+        };
+        if balance > 0 && !force.unwrap_or(false) {
+            env::panic(b"Can't unregister the account with the positive balance without force");
+        }
+        // locked tokens belong to the schedule, revoke or claim it first
+        assert!(
+            self.vesting_schedules.get(&account_id).is_none(),
+            "Can't unregister the account with a vesting schedule"
+        );
+        // past snapshots keep the balance it had
+        self.internal_checkpoint(&account_id);
+        let allowances = self.internal_remove_allowances(&account_id);
+        self.accounts.remove(&account_id);
+        if balance > 0 {
+            self.total_supply -= balance;
+            FtEventKind::FtBurn(vec![FtBurnLog {
+                owner_id: account_id.clone(),
+                amount: balance.into(),
+                memo: Some("Account unregistered".to_string()),
+            }])
+            .emit();
+        }
+        let refund = self.storage_minimum_balance().0 + Balance::from(allowances) * self.ft_allowance_storage_cost().0;
+        Promise::new(account_id).transfer(refund);
+        true
+    }
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: self.storage_minimum_balance(),
+            max: Some(self.storage_minimum_balance()),
         }
     }
 
@@ -74,21 +143,21 @@ impl StorageManager for Contract {
         (Balance::from(self.account_storage_usage) * STORAGE_PRICE_PER_BYTE).into()
     }
 
-    fn storage_balance_of(&self, account_id: ValidAccountId) -> AccountStorageBalance {
-        if let Some(balance) = self.accounts.get(account_id.as_ref()) {
-            AccountStorageBalance {
+    fn storage_balance_of(&self, account_id: ValidAccountId) -> Option<AccountStorageBalance> {
+        self.internal_storage_balance_of(account_id.as_ref())
+    }
+}
+
+impl Contract {
+    /// the whole minimum balance is used by the account entry, nothing is ever available
+    pub(crate) fn internal_storage_balance_of(&self, account_id: &AccountId) -> Option<AccountStorageBalance> {
+        if self.accounts.contains_key(account_id) {
+            Some(AccountStorageBalance {
                 total: self.storage_minimum_balance(),
-                available: if balance > 0 {
-                    0.into()
-                } else {
-                    self.storage_minimum_balance()
-                },
-            }
-        } else {
-            AccountStorageBalance {
-                total: 0.into(),
                 available: 0.into(),
-            }
+            })
+        } else {
+            None
         }
     }
 }
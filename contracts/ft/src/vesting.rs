@@ -0,0 +1,196 @@
+use crate::*;
+use near_sdk::json_types::{ValidAccountId, U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+
+/// 30 days in ms
+pub const VESTING_MONTH: u64 = 30 * 24 * 60 * 60 * 1000;
+
+pub(crate) fn now_ms() -> u64 {
+    env::block_timestamp() / 1_000_000
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum VestingSteps {
+    /// vests continuously between start and end
+    Linear,
+    /// vests at every full VESTING_MONTH after start (and fully at end)
+    Monthly,
+}
+
+/// Tokens are in the account's balance from the start, what isn't claimed yet is locked.
+/// Times in ms.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct VestingSchedule {
+    pub total: U128,
+    pub claimed: U128,
+    pub start: U64,
+    /// nothing vests before the cliff, at the cliff everything since start vests
+    pub cliff: U64,
+    pub end: U64,
+    pub steps: VestingSteps,
+    /// owner can take back what hasn't vested yet
+    pub revocable: bool,
+}
+
+impl VestingSchedule {
+    pub fn vested_at(&self, now: u64) -> Balance {
+        if now < self.cliff.0 {
+            return 0;
+        }
+        if now >= self.end.0 {
+            return self.total.0;
+        }
+        let mut elapsed = now - self.start.0;
+        if self.steps == VestingSteps::Monthly {
+            elapsed -= elapsed % VESTING_MONTH;
+        }
+        self.total.0 * Balance::from(elapsed) / Balance::from(self.end.0 - self.start.0)
+    }
+
+    pub fn locked(&self) -> Balance {
+        self.total.0 - self.claimed.0
+    }
+}
+
+/// create_vesting_schedule args, times in ms
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct VestingScheduleArgs {
+    pub total: U128,
+    pub start: U64,
+    pub cliff: U64,
+    pub end: U64,
+    pub steps: VestingSteps,
+    pub revocable: bool,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct VestingBalance {
+    pub vested: U128,
+    pub claimed: U128,
+    pub locked: U128,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// only owner, moves `total` from the owner to `account_id` locked under the schedule,
+    /// attach a deposit for the schedule's storage (the rest is refunded, the storage is refunded once
+    /// the schedule is fully claimed or revoked)
+    #[payable]
+    pub fn create_vesting_schedule(&mut self, account_id: ValidAccountId, schedule: VestingScheduleArgs) {
+        self.assert_owner();
+        let VestingScheduleArgs { total, start, cliff, end, steps, revocable } = schedule;
+        assert!(total.0 > 0, "The amount should be a positive number");
+        assert!(start.0 < end.0, "start must be before end");
+        assert!(start.0 <= cliff.0 && cliff.0 <= end.0, "cliff must be between start and end");
+        assert!(
+            self.vesting_schedules.get(account_id.as_ref()).is_none(),
+            "The account already has a vesting schedule"
+        );
+
+        let owner_id = self.owner_id.clone();
+        self.internal_transfer(&owner_id, account_id.as_ref(), total.0, Some("vesting".to_string()));
+        let initial_storage_usage = env::storage_usage();
+        self.vesting_schedules.insert(account_id.as_ref(), &VestingSchedule {
+            total,
+            claimed: 0.into(),
+            start,
+            cliff,
+            end,
+            steps,
+            revocable,
+        });
+        let storage_cost = Balance::from(env::storage_usage() - initial_storage_usage) * STORAGE_PRICE_PER_BYTE;
+        let deposit = env::attached_deposit();
+        assert!(
+            deposit >= storage_cost,
+            "Requires attached deposit of at least {} to cover the schedule storage",
+            storage_cost
+        );
+        if deposit > storage_cost {
+            Promise::new(owner_id).transfer(deposit - storage_cost);
+        }
+    }
+
+    /// unlocks everything vested so far, returns the newly claimed amount, a fully claimed schedule is removed
+    pub fn claim_vested(&mut self) -> U128 {
+        let account_id = env::predecessor_account_id();
+        let mut schedule = self.vesting_schedules.get(&account_id).expect("No vesting schedule");
+        let claimable = schedule.vested_at(now_ms()) - schedule.claimed.0;
+        assert!(claimable > 0, "Nothing to claim");
+        schedule.claimed = (schedule.claimed.0 + claimable).into();
+        if schedule.claimed == schedule.total {
+            self.internal_remove_vesting_schedule(&account_id);
+        } else {
+            self.vesting_schedules.insert(&account_id, &schedule);
+        }
+        claimable.into()
+    }
+
+    /// only owner, takes back what hasn't vested yet and ends the schedule now
+    pub fn revoke_vesting_schedule(&mut self, account_id: ValidAccountId) -> U128 {
+        self.assert_owner();
+        let mut schedule = self.vesting_schedules.get(account_id.as_ref()).expect("No vesting schedule");
+        assert!(schedule.revocable, "The vesting schedule is not revocable");
+        let now = now_ms();
+        let vested = schedule.vested_at(now);
+        let unvested = schedule.total.0 - vested;
+
+        // shrink the schedule first so the unvested tokens are no longer locked
+        if vested == schedule.claimed.0 {
+            self.internal_remove_vesting_schedule(account_id.as_ref());
+        } else {
+            schedule.total = vested.into();
+            schedule.end = now.into();
+            self.vesting_schedules.insert(account_id.as_ref(), &schedule);
+        }
+        if unvested > 0 {
+            let owner_id = self.owner_id.clone();
+            self.internal_transfer(account_id.as_ref(), &owner_id, unvested, Some("vesting revoked".to_string()));
+        }
+        unvested.into()
+    }
+
+    /// views
+
+    pub fn ft_vesting_schedule(&self, account_id: ValidAccountId) -> Option<VestingSchedule> {
+        self.vesting_schedules.get(account_id.as_ref())
+    }
+
+    pub fn ft_vesting_balance(&self, account_id: ValidAccountId) -> VestingBalance {
+        if let Some(schedule) = self.vesting_schedules.get(account_id.as_ref()) {
+            VestingBalance {
+                vested: schedule.vested_at(now_ms()).into(),
+                claimed: schedule.claimed,
+                locked: schedule.locked().into(),
+            }
+        } else {
+            VestingBalance {
+                vested: 0.into(),
+                claimed: 0.into(),
+                locked: 0.into(),
+            }
+        }
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_locked_balance(&self, account_id: &AccountId) -> Balance {
+        self.vesting_schedules
+            .get(account_id)
+            .map(|schedule| schedule.locked())
+            .unwrap_or(0)
+    }
+
+    /// the owner paid for the schedule's storage, refunds it
+    fn internal_remove_vesting_schedule(&mut self, account_id: &AccountId) {
+        let initial_storage_usage = env::storage_usage();
+        self.vesting_schedules.remove(account_id);
+        let refund = Balance::from(initial_storage_usage - env::storage_usage()) * STORAGE_PRICE_PER_BYTE;
+        Promise::new(self.owner_id.clone()).transfer(refund);
+    }
+}
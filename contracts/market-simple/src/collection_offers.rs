@@ -0,0 +1,330 @@
+use crate::*;
+
+/// CUSTOM - escrowed offers on any token of a token_type, filled by token owners through nft_approve
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CollectionOffer {
+    pub offer_id: U64,
+    pub owner_id: AccountId,
+    pub nft_contract_id: AccountId,
+    pub token_type: String,
+    pub ft_token_id: FungibleTokenId,
+    /// per token
+    pub price: U128,
+    /// tokens still wanted, price * quantity is held in escrow
+    pub quantity: u64,
+    pub created_at: U64,
+}
+
+/// ft_transfer_call msg `{"collection_offer": {...}}`, the amount must cover price * quantity
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CollectionOfferArgs {
+    pub nft_contract_id: ValidAccountId,
+    pub token_type: String,
+    pub price: U128,
+    pub quantity: u64,
+}
+
+/// nft_approve msg `{"fill_offer": "<offer_id>"}`, token_type is added by the NFT contract
+/// attach enough gas to nft_approve for the token lookup, transfer and payout (~200 Tgas)
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FillOfferArgs {
+    pub fill_offer: U64,
+    pub token_type: TokenType,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// escrow NEAR for `quantity` tokens of `token_type` at `price` each,
+    /// FT offers go through ft_transfer_call with a collection_offer msg
+    #[payable]
+    pub fn make_collection_offer(
+        &mut self,
+        nft_contract_id: ValidAccountId,
+        token_type: String,
+        price: U128,
+        quantity: u64,
+    ) -> U64 {
+        let total = price.0 * u128::from(quantity);
+        assert_eq!(
+            env::attached_deposit(),
+            total,
+            "Attached deposit must be price * quantity: {}",
+            total
+        );
+        self.internal_add_collection_offer(
+            env::predecessor_account_id(),
+            CollectionOfferArgs {
+                nft_contract_id,
+                token_type,
+                price,
+                quantity,
+            },
+            "near".to_string(),
+        )
+    }
+
+    /// offer owner gets the escrow for the remaining quantity back
+    #[payable]
+    pub fn cancel_collection_offer(&mut self, offer_id: U64) {
+        assert_one_yocto();
+        let offer = self.collection_offers.get(&offer_id.0).expect("No collection offer");
+        assert_eq!(
+            env::predecessor_account_id(),
+            offer.owner_id,
+            "Must be offer owner"
+        );
+        self.internal_remove_collection_offer(&offer);
+        transfer_token(&offer.owner_id, &offer.ft_token_id, offer.price.0 * u128::from(offer.quantity));
+    }
+
+    /// views
+
+    pub fn get_collection_offer(&self, offer_id: U64) -> Option<CollectionOffer> {
+        self.collection_offers.get(&offer_id.0)
+    }
+
+    pub fn get_supply_collection_offers_by_type(
+        &self,
+        nft_contract_id: AccountId,
+        token_type: String,
+    ) -> U64 {
+        let key = format!("{}{}{}", nft_contract_id, DELIMETER, token_type);
+        U64(self.collection_offers_by_type.get(&key).map(|offers| offers.len()).unwrap_or(0))
+    }
+
+    pub fn get_collection_offers_by_type(
+        &self,
+        nft_contract_id: AccountId,
+        token_type: String,
+        from_index: U64,
+        limit: u64,
+    ) -> Vec<CollectionOffer> {
+        let key = format!("{}{}{}", nft_contract_id, DELIMETER, token_type);
+        if let Some(offer_ids) = self.collection_offers_by_type.get(&key) {
+            self.collection_offers_page(&offer_ids, from_index, limit)
+        } else {
+            vec![]
+        }
+    }
+
+    pub fn get_collection_offers_by_owner_id(
+        &self,
+        account_id: AccountId,
+        from_index: U64,
+        limit: u64,
+    ) -> Vec<CollectionOffer> {
+        if let Some(offer_ids) = self.collection_offers_by_owner_id.get(&account_id) {
+            self.collection_offers_page(&offer_ids, from_index, limit)
+        } else {
+            vec![]
+        }
+    }
+}
+
+impl Contract {
+    fn collection_offers_page(
+        &self,
+        offer_ids: &UnorderedSet<u64>,
+        from_index: U64,
+        limit: u64,
+    ) -> Vec<CollectionOffer> {
+        let keys = offer_ids.as_vector();
+        let start = u64::from(from_index);
+        let end = min(start + limit, keys.len());
+        (start..end)
+            .map(|i| self.collection_offers.get(&keys.get(i).unwrap()).unwrap())
+            .collect()
+    }
+
+    pub(crate) fn internal_add_collection_offer(
+        &mut self,
+        owner_id: AccountId,
+        args: CollectionOfferArgs,
+        ft_token_id: FungibleTokenId,
+    ) -> U64 {
+        let CollectionOfferArgs {
+            nft_contract_id,
+            token_type,
+            price,
+            quantity,
+        } = args;
+        assert!(price.0 > 0, "Price must be greater than 0");
+        assert!(quantity > 0, "Quantity must be greater than 0");
+        self.assert_ft_token_accepted(&ft_token_id);
+
+        let initial_storage_usage = env::storage_usage();
+
+        let offer_id = self.next_collection_offer_id;
+        self.next_collection_offer_id += 1;
+        let offer = CollectionOffer {
+            offer_id: U64(offer_id),
+            owner_id: owner_id.clone(),
+            nft_contract_id: nft_contract_id.into(),
+            token_type,
+            ft_token_id,
+            price,
+            quantity,
+            created_at: U64(now_ms()),
+        };
+        self.collection_offers.insert(&offer_id, &offer);
+
+        let type_key = format!("{}{}{}", offer.nft_contract_id, DELIMETER, offer.token_type);
+        let mut by_type = self.collection_offers_by_type.get(&type_key).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::CollectionOffersByTypeInner {
+                    token_type_hash: hash_account_id(&type_key),
+                }
+                .try_to_vec()
+                .unwrap(),
+            )
+        });
+        by_type.insert(&offer_id);
+        self.collection_offers_by_type.insert(&type_key, &by_type);
+
+        let mut by_owner_id = self.collection_offers_by_owner_id.get(&owner_id).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::CollectionOffersByOwnerIdInner {
+                    account_id_hash: hash_account_id(&owner_id),
+                }
+                .try_to_vec()
+                .unwrap(),
+            )
+        });
+        by_owner_id.insert(&offer_id);
+        self.collection_offers_by_owner_id.insert(&owner_id, &by_owner_id);
+
+        self.internal_charge_storage(&owner_id, initial_storage_usage);
+
+        MarketEventKind::CollectionOfferCreated(vec![offer]).emit();
+
+        U64(offer_id)
+    }
+
+    pub(crate) fn internal_remove_collection_offer(&mut self, offer: &CollectionOffer) {
+        let initial_storage_usage = env::storage_usage();
+        self.collection_offers.remove(&offer.offer_id.0);
+
+        let type_key = format!("{}{}{}", offer.nft_contract_id, DELIMETER, offer.token_type);
+        let mut by_type = self.collection_offers_by_type.get(&type_key).expect("No offer by type");
+        by_type.remove(&offer.offer_id.0);
+        if by_type.is_empty() {
+            self.collection_offers_by_type.remove(&type_key);
+        } else {
+            self.collection_offers_by_type.insert(&type_key, &by_type);
+        }
+
+        let mut by_owner_id = self.collection_offers_by_owner_id.get(&offer.owner_id).expect("No offer by owner_id");
+        by_owner_id.remove(&offer.offer_id.0);
+        if by_owner_id.is_empty() {
+            self.collection_offers_by_owner_id.remove(&offer.owner_id);
+        } else {
+            self.collection_offers_by_owner_id.insert(&offer.owner_id, &by_owner_id);
+        }
+
+        self.internal_charge_storage(&offer.owner_id, initial_storage_usage);
+
+        MarketEventKind::CollectionOfferRemoved(vec![CollectionOfferRemovedLog {
+            offer_id: offer.offer_id,
+            owner_id: offer.owner_id.clone(),
+        }])
+        .emit();
+    }
+
+    /// token owner approved the market with a fill_offer msg, the token_type in the msg can be set by
+    /// the owner so the token is looked up on the NFT contract before selling it into the offer
+    pub(crate) fn internal_fill_collection_offer(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        owner_id: AccountId,
+        approval_id: u64,
+        args: FillOfferArgs,
+    ) -> Promise {
+        let offer = self.collection_offers.get(&args.fill_offer.0).expect("No collection offer");
+        assert_eq!(offer.nft_contract_id, nft_contract_id, "Offer is for another NFT contract");
+        assert_eq!(args.token_type.as_ref(), Some(&offer.token_type), "Token is not of the offer's token_type");
+        assert_ne!(offer.owner_id, owner_id, "Cannot fill your own offer");
+
+        ext_contract::nft_token(
+            token_id.clone(),
+            &nft_contract_id,
+            NO_DEPOSIT,
+            GAS_FOR_NFT_TOKEN,
+        )
+        .then(ext_self::resolve_fill_collection_offer(
+            FillOffer {
+                offer_id: args.fill_offer,
+                nft_contract_id,
+                token_id,
+                owner_id,
+                approval_id,
+            },
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_RESOLVE_FILL_OFFER,
+        ))
+    }
+}
+
+/// a token owner's fill_offer waiting for the NFT contract's nft_token
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FillOffer {
+    pub offer_id: U64,
+    pub nft_contract_id: AccountId,
+    pub token_id: TokenId,
+    pub owner_id: AccountId,
+    pub approval_id: u64,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// self callback
+
+    /// sells the token into the offer if the NFT contract confirms the owner, the approval and the token_type
+    #[private]
+    pub fn resolve_fill_collection_offer(&mut self, fill_offer: FillOffer) -> bool {
+        let token = if let Some(Some(token)) = promise_result_as_token() {
+            token
+        } else {
+            return false;
+        };
+        // the offer may have been filled or cancelled in the meantime
+        let mut offer = if let Some(offer) = self.collection_offers.get(&fill_offer.offer_id.0) {
+            offer
+        } else {
+            return false;
+        };
+        if token.owner_id != fill_offer.owner_id
+            || token.approved_account_ids.get(&env::current_account_id()) != Some(&fill_offer.approval_id)
+            || token.token_type.as_ref() != Some(&offer.token_type)
+        {
+            return false;
+        }
+
+        // take the token out of the offer up front, a failed transfer refunds its escrow in resolve_offer_purchase
+        offer.quantity -= 1;
+        if offer.quantity == 0 {
+            self.internal_remove_collection_offer(&offer);
+        } else {
+            self.collection_offers.insert(&offer.offer_id.0, &offer);
+        }
+
+        self.internal_escrow_purchase(
+            OfferPurchase {
+                buyer_id: offer.owner_id,
+                seller_id: fill_offer.owner_id,
+                nft_contract_id: fill_offer.nft_contract_id,
+                token_id: fill_offer.token_id,
+                ft_token_id: offer.ft_token_id,
+                price: offer.price,
+            },
+            fill_offer.approval_id,
+        );
+        true
+    }
+}
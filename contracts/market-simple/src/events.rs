@@ -0,0 +1,146 @@
+//! sale, bid and offer events (NEP-297)
+
+use crate::*;
+
+pub const MARKET_STANDARD_NAME: &str = "nft_market";
+pub const MARKET_STANDARD_VERSION: &str = "1.0.0";
+const EVENT_JSON_PREFIX: &str = "EVENT_JSON:";
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+pub enum MarketEventKind {
+    SaleCreated(Vec<SaleCreatedLog>),
+    PriceUpdated(Vec<PriceUpdatedLog>),
+    BidPlaced(Vec<BidLog>),
+    BidRefunded(Vec<BidLog>),
+    SaleRemoved(Vec<SaleRemovedLog>),
+    PurchaseCompleted(Vec<PurchaseLog>),
+    PayoutFailed(Vec<PayoutFailedLog>),
+    CollectionOfferCreated(Vec<CollectionOffer>),
+    CollectionOfferRemoved(Vec<CollectionOfferRemovedLog>),
+    TokenOfferCreated(Vec<TokenOffer>),
+    TokenOfferRemoved(Vec<TokenOfferRemovedLog>),
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MarketEvent {
+    pub standard: String,
+    pub version: String,
+    #[serde(flatten)]
+    pub event: MarketEventKind,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleCreatedLog {
+    pub owner_id: AccountId,
+    pub nft_contract_id: AccountId,
+    pub token_id: TokenId,
+    pub approval_id: u64,
+    pub sale_conditions: SaleConditions,
+    pub is_auction: bool,
+    pub token_type: TokenType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ends_at: Option<U64>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceUpdatedLog {
+    pub owner_id: AccountId,
+    pub nft_contract_id: AccountId,
+    pub token_id: TokenId,
+    pub ft_token_id: FungibleTokenId,
+    pub price: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BidLog {
+    pub bidder_id: AccountId,
+    pub nft_contract_id: AccountId,
+    pub token_id: TokenId,
+    pub ft_token_id: FungibleTokenId,
+    pub price: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleRemovedLog {
+    pub owner_id: AccountId,
+    pub nft_contract_id: AccountId,
+    pub token_id: TokenId,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PurchaseLog {
+    pub buyer_id: AccountId,
+    pub seller_id: AccountId,
+    pub nft_contract_id: AccountId,
+    pub token_id: TokenId,
+    pub ft_token_id: FungibleTokenId,
+    pub price: U128,
+    pub market_fee: U128,
+    pub payout: Payout,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PayoutFailedLog {
+    pub buyer_id: AccountId,
+    pub seller_id: AccountId,
+    pub nft_contract_id: AccountId,
+    pub token_id: TokenId,
+    pub ft_token_id: FungibleTokenId,
+    pub price: U128,
+    pub reason: PayoutFailureReason,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CollectionOfferRemovedLog {
+    pub offer_id: U64,
+    pub owner_id: AccountId,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenOfferRemovedLog {
+    pub owner_id: AccountId,
+    pub nft_contract_id: AccountId,
+    pub token_id: TokenId,
+}
+
+#[derive(Serialize, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum PayoutFailureReason {
+    /// nft_transfer_payout failed, the token most likely never left the seller
+    TransferFailed,
+    /// payout could not be parsed, is empty or doesn't add up to the price
+    InvalidPayout,
+    /// payout receivers plus bid refunds don't fit in the gas for resolve_purchase
+    TooManyReceivers,
+}
+
+impl MarketEventKind {
+    pub(crate) fn emit(self) {
+        let event = MarketEvent {
+            standard: MARKET_STANDARD_NAME.to_string(),
+            version: MARKET_STANDARD_VERSION.to_string(),
+            event: self,
+        };
+        env::log(
+            format!(
+                "{}{}",
+                EVENT_JSON_PREFIX,
+                near_sdk::serde_json::to_string(&event).unwrap()
+            )
+            .as_bytes(),
+        );
+    }
+}
@@ -1,3 +1,6 @@
+// ext_contract adds the account, deposit and gas to every call, nft_transfer_payout ends up with 9 args
+#![allow(clippy::too_many_arguments)]
+
 use crate::*;
 
 /// external contract calls
@@ -19,4 +22,16 @@ trait ExtContract {
         amount: U128,
         memo: Option<String>
     );
+    fn nft_token(&self, token_id: TokenId);
+}
+
+/// the part of the NFT contract's nft_token view needed to check a sale or collection offer fill is valid
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonToken {
+    pub owner_id: AccountId,
+    pub approved_account_ids: HashMap<AccountId, u64>,
+    /// CUSTOM - nft-simple tokens have a token_type
+    #[serde(default)]
+    pub token_type: Option<String>,
 }
\ No newline at end of file
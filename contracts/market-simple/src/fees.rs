@@ -0,0 +1,90 @@
+use crate::*;
+
+/// CUSTOM - market fee taken from every sale before the NFT contract splits the payout
+
+/// hard cap of 10%
+pub const MARKET_FEE_BPS_CAP: u32 = 1000;
+
+pub(crate) fn market_fee_for_price(market_fee_bps: u32, price: Balance) -> Balance {
+    price * u128::from(market_fee_bps) / 10_000u128
+}
+
+#[near_bindgen]
+impl Contract {
+    /// only owner
+    pub fn set_market_fee(&mut self, market_fee_bps: u32) {
+        self.assert_owner();
+        assert!(market_fee_bps <= MARKET_FEE_BPS_CAP, "Market fee limited to {} bps", MARKET_FEE_BPS_CAP);
+        self.market_fee_bps = market_fee_bps;
+    }
+
+    /// only owner, pays out fees collected in NEAR or an FT to the owner
+    #[payable]
+    pub fn withdraw_fees(&mut self, ft_token_id: ValidAccountId, amount: U128) -> Promise {
+        assert_one_yocto();
+        self.assert_owner();
+        let ft_token_id: AccountId = ft_token_id.into();
+        self.internal_withdraw_fees(&ft_token_id, amount.0);
+        if ft_token_id == "near" {
+            Promise::new(self.owner_id.clone()).transfer(amount.0)
+        } else {
+            ext_contract::ft_transfer(
+                self.owner_id.clone(),
+                amount,
+                None,
+                &ft_token_id,
+                1,
+                GAS_FOR_FT_TRANSFER,
+            )
+            .then(ext_self_fees::resolve_withdraw_fees(
+                ft_token_id,
+                amount,
+                &env::current_account_id(),
+                NO_DEPOSIT,
+                GAS_FOR_RESOLVE_WITHDRAW_FEES,
+            ))
+        }
+    }
+
+    /// self callback, puts the fees back in the treasury if the ft_transfer failed
+    #[private]
+    pub fn resolve_withdraw_fees(&mut self, ft_token_id: AccountId, amount: U128) -> bool {
+        if near_sdk::is_promise_success() {
+            return true;
+        }
+        self.internal_deposit_fees(&ft_token_id, amount.0);
+        false
+    }
+
+    /// views
+
+    pub fn get_market_fee(&self) -> u32 {
+        self.market_fee_bps
+    }
+
+    pub fn get_fee_balance(&self, ft_token_id: AccountId) -> U128 {
+        U128(self.treasury.get(&ft_token_id).unwrap_or(0))
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_deposit_fees(&mut self, ft_token_id: &AccountId, amount: Balance) {
+        let balance = self.treasury.get(ft_token_id).unwrap_or(0);
+        self.treasury.insert(ft_token_id, &(balance + amount));
+    }
+
+    pub(crate) fn internal_withdraw_fees(&mut self, ft_token_id: &AccountId, amount: Balance) {
+        let balance = self.treasury.get(ft_token_id).unwrap_or(0);
+        assert!(amount > 0 && amount <= balance, "Not enough fees collected: {}", balance);
+        if balance == amount {
+            self.treasury.remove(ft_token_id);
+        } else {
+            self.treasury.insert(ft_token_id, &(balance - amount));
+        }
+    }
+}
+
+#[ext_contract(ext_self_fees)]
+trait ExtSelfFees {
+    fn resolve_withdraw_fees(&mut self, ft_token_id: AccountId, amount: U128) -> bool;
+}
@@ -2,17 +2,42 @@ use crate::*;
 
 /// callbacks from FT Contracts
 
-trait FungibleTokenReceiver {
+/// ft_transfer_call msg, either a collection offer, a token offer or a purchase / bid on a sale
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(untagged)]
+enum FtOnTransferArgs {
+    CollectionOffer { collection_offer: CollectionOfferArgs },
+    TokenOffer { token_offer: TokenOfferArgs },
+    Purchase(PurchaseArgs),
+}
+
+pub trait FungibleTokenReceiver {
     fn ft_on_transfer(&mut self, sender_id: AccountId, amount: U128, msg: String) -> PromiseOrValue<U128>;
 }
 
 #[near_bindgen]
 impl FungibleTokenReceiver for Contract {
     fn ft_on_transfer(&mut self, sender_id: AccountId, amount: U128, msg: String) -> PromiseOrValue<U128> {
+        let args = near_sdk::serde_json::from_str(&msg).expect("Invalid PurchaseArgs");
         let PurchaseArgs {
             nft_contract_id,
             token_id,
-        } = near_sdk::serde_json::from_str(&msg).expect("Invalid PurchaseArgs");
+        } = match args {
+            // CUSTOM - escrow a collection offer, anything above price * quantity is returned
+            FtOnTransferArgs::CollectionOffer { collection_offer } => {
+                let total = collection_offer.price.0 * u128::from(collection_offer.quantity);
+                assert!(amount.0 >= total, "Amount must cover price * quantity: {}", total);
+                self.internal_add_collection_offer(sender_id, collection_offer, env::predecessor_account_id());
+                return PromiseOrValue::Value(U128(amount.0 - total));
+            }
+            // CUSTOM - escrow an offer on an unlisted token
+            FtOnTransferArgs::TokenOffer { token_offer } => {
+                self.internal_add_token_offer(sender_id, token_offer, env::predecessor_account_id(), amount.0);
+                return PromiseOrValue::Value(U128(0));
+            }
+            FtOnTransferArgs::Purchase(purchase_args) => purchase_args,
+        };
 
         let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMETER, token_id);
         let mut sale = self
@@ -30,6 +55,33 @@ impl FungibleTokenReceiver for Contract {
 
         assert!(amount.0 > 0, "Amount must be greater than 0");
 
+        // CUSTOM - dutch auctions sell at the current price and refund the rest
+        if let Some(current_price) = sale.dutch_price(&ft_token_id) {
+            assert!(
+                amount.0 >= current_price,
+                "Amount must be at least the current price: {}",
+                current_price
+            );
+            if amount.0 > current_price {
+                ext_contract::ft_transfer(
+                    sender_id.clone(),
+                    U128(amount.0 - current_price),
+                    None,
+                    &ft_token_id,
+                    1,
+                    GAS_FOR_FT_TRANSFER,
+                );
+            }
+            return self.process_purchase(
+                nft_contract_id.into(),
+                token_id,
+                ft_token_id,
+                U128(current_price),
+                sender_id,
+                false,
+            ).into();
+        }
+
         if !sale.is_auction && amount == price {
             self.process_purchase(
                 nft_contract_id.into(),
@@ -37,6 +89,7 @@ impl FungibleTokenReceiver for Contract {
                 ft_token_id,
                 price,
                 sender_id,
+                false,
             ).into()
         } else {
             if sale.is_auction && price.0 > 0 {
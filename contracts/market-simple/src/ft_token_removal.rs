@@ -0,0 +1,108 @@
+use crate::*;
+
+/// CUSTOM - owner stops supporting an FT. From the call on no new listings, bids or offers are taken in it,
+/// sales can still be bought at their listed price until the grace period ends. After that anyone can
+/// process the removal: the FT is stripped from sale_conditions (and dutch auctions), its bids are refunded
+/// and sales left without a price are removed. Escrowed offers in the FT can still be filled or cancelled.
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtTokenRemoval {
+    pub ft_token_id: FungibleTokenId,
+    /// ms
+    pub removes_at: U64,
+    /// sales are processed from the end, None until processing starts
+    pub next_sale_index: Option<U64>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// only owner, grace_period in ms (none removes as soon as the removal is processed)
+    pub fn remove_ft_token_ids(&mut self, ft_token_ids: Vec<ValidAccountId>, grace_period: Option<U64>) {
+        self.assert_owner();
+        let removes_at = U64(now_ms() + grace_period.map(|g| g.0).unwrap_or(0));
+        for ft_token_id in ft_token_ids {
+            let ft_token_id: AccountId = ft_token_id.into();
+            assert_ne!(ft_token_id, "near", "NEAR can't be removed");
+            assert!(self.ft_token_ids.contains(&ft_token_id), "Token {} not supported by this market", ft_token_id);
+            assert!(self.ft_token_removals.get(&ft_token_id).is_none(), "Token {} already pending removal", ft_token_id);
+            self.ft_token_removals.insert(&ft_token_id, &FtTokenRemoval {
+                ft_token_id: ft_token_id.clone(),
+                removes_at,
+                next_sale_index: None,
+            });
+        }
+    }
+
+    /// anyone after removes_at, processes up to limit sales, true once the FT is fully removed
+    pub fn process_ft_token_removal(&mut self, ft_token_id: ValidAccountId, limit: u64) -> bool {
+        let ft_token_id: AccountId = ft_token_id.into();
+        let mut removal = self.ft_token_removals.get(&ft_token_id).expect("Token not pending removal");
+        assert!(now_ms() >= removal.removes_at.0, "Grace period has not ended");
+        // only converted sales are processed
+        assert!(self.legacy_sales.is_empty(), "Sales not migrated yet");
+
+        if removal.next_sale_index.is_none() {
+            self.ft_token_ids.remove(&ft_token_id);
+        }
+        // going down from the end, sales removed on the way (or by buyers meanwhile) only swap in sales already processed
+        let mut index = min(
+            removal.next_sale_index.map(|i| i.0).unwrap_or_else(|| self.sales.len()),
+            self.sales.len(),
+        );
+        let stop = index.saturating_sub(limit);
+        while index > stop {
+            index -= 1;
+            let contract_and_token_id = self.sales.keys_as_vector().get(index).unwrap();
+            self.internal_strip_ft_from_sale(&contract_and_token_id, &ft_token_id);
+        }
+
+        if index == 0 {
+            self.ft_token_removals.remove(&ft_token_id);
+            true
+        } else {
+            removal.next_sale_index = Some(U64(index));
+            self.ft_token_removals.insert(&ft_token_id, &removal);
+            false
+        }
+    }
+
+    /// views
+
+    pub fn get_ft_token_ids_pending_removal(&self) -> Vec<FtTokenRemoval> {
+        self.ft_token_removals.values().collect()
+    }
+}
+
+impl Contract {
+    /// new listings, bids and offers only in FTs that are supported and not pending removal
+    pub(crate) fn assert_ft_token_accepted(&self, ft_token_id: &FungibleTokenId) {
+        if !self.ft_token_ids.contains(ft_token_id) || self.ft_token_removals.get(ft_token_id).is_some() {
+            env::panic(format!("Token {} not supported by this market", ft_token_id).as_bytes());
+        }
+    }
+
+    fn internal_strip_ft_from_sale(&mut self, contract_and_token_id: &ContractAndTokenId, ft_token_id: &FungibleTokenId) {
+        let mut sale = self.sales.get(contract_and_token_id).unwrap();
+        if !sale.sale_conditions.contains_key(ft_token_id) {
+            return;
+        }
+        // nothing left to sell for, remove the sale and refund its bids
+        if sale.sale_conditions.len() == 1 {
+            let sale = self.internal_remove_sale(sale.nft_contract_id, sale.token_id);
+            self.refund_all_bids(&sale);
+            return;
+        }
+
+        sale.sale_conditions.remove(ft_token_id);
+        if let Some(dutch_auction) = sale.dutch_auction.as_mut() {
+            dutch_auction.remove(ft_token_id);
+        }
+        if let Some(bids) = sale.bids.remove(ft_token_id) {
+            if let Some(bid) = bids.last() {
+                self.internal_refund_bid(&sale.nft_contract_id, &sale.token_id, ft_token_id, bid);
+            }
+        }
+        self.internal_update_sale(contract_and_token_id, &sale);
+    }
+}
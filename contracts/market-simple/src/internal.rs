@@ -1,4 +1,5 @@
 use crate::*;
+use std::cmp::Ordering;
 
 pub(crate) fn hash_account_id(account_id: &AccountId) -> CryptoHash {
     let mut hash = CryptoHash::default();
@@ -6,6 +7,96 @@ pub(crate) fn hash_account_id(account_id: &AccountId) -> CryptoHash {
     hash
 }
 
+pub(crate) fn now_ms() -> u64 {
+    env::block_timestamp() / 1_000_000
+}
+
+/// reads the result of an nft_token call, None if the call failed or returned something else
+pub(crate) fn promise_result_as_token() -> Option<Option<JsonToken>> {
+    near_sdk::promise_result_as_success()
+        .and_then(|value| near_sdk::serde_json::from_slice::<Option<JsonToken>>(&value).ok())
+}
+
+/// the token still belongs to the seller and the market's approval is the one the sale was listed with
+pub(crate) fn is_sale_valid_for_token(sale: &Sale, token: &Option<JsonToken>) -> bool {
+    token
+        .as_ref()
+        .map(|token| {
+            token.owner_id == sale.owner_id
+                && token.approved_account_ids.get(&env::current_account_id()) == Some(&sale.approval_id)
+        })
+        .unwrap_or(false)
+}
+
+/// send NEAR or an FT from the market's balance
+pub(crate) fn transfer_token(receiver_id: &AccountId, ft_token_id: &FungibleTokenId, amount: Balance) {
+    if ft_token_id == "near" {
+        Promise::new(receiver_id.clone()).transfer(amount);
+    } else {
+        ext_contract::ft_transfer(
+            receiver_id.clone(),
+            U128(amount),
+            None,
+            ft_token_id,
+            1,
+            GAS_FOR_FT_TRANSFER,
+        );
+    }
+}
+
+/// storage of a bid, charged to the bidder while the bid is kept in the sale's bid history
+/// a bid is at most a 64 byte account id and a price
+pub(crate) const MAX_BYTES_FOR_BID: StorageUsage = 4 + 64 + 16;
+
+pub(crate) fn bytes_for_bids(bids: &Bids) -> StorageUsage {
+    bids.try_to_vec().unwrap().len() as StorageUsage
+}
+
+/// bid storage the sale owner pays for, a full bid history in every FT of the sale
+pub(crate) fn bytes_reserved_for_bids(sale: &Sale, bid_history_length: u8) -> StorageUsage {
+    let reserved = 4 + sale
+        .sale_conditions
+        .keys()
+        .map(|ft_token_id| {
+            4 + ft_token_id.len() as StorageUsage + 4 + StorageUsage::from(bid_history_length) * MAX_BYTES_FOR_BID
+        })
+        .sum::<StorageUsage>();
+    max(reserved, bytes_for_bids(&sale.bids))
+}
+
+/// reads the Payout returned by nft_transfer_payout and checks it splits the price minus the market fee,
+/// other_transfers (e.g. bid refunds) have to fit in the same callback
+pub(crate) fn payout_from_promise_result(
+    price: Balance,
+    market_fee: Balance,
+    other_transfers: usize,
+) -> Result<Payout, PayoutFailureReason> {
+    let value = near_sdk::promise_result_as_success().ok_or(PayoutFailureReason::TransferFailed)?;
+    // a bad payout from bad NFT contract
+    let payout = near_sdk::serde_json::from_slice::<Payout>(&value)
+        .map_err(|_| PayoutFailureReason::InvalidPayout)?;
+    // gas to do 10 FT transfers (and definitely 10 NEAR transfers)
+    if payout.len() + other_transfers > 10 {
+        return Err(PayoutFailureReason::TooManyReceivers);
+    }
+    if payout.is_empty() {
+        return Err(PayoutFailureReason::InvalidPayout);
+    }
+    // TODO off by 1 e.g. payouts are fractions of 3333 + 3333 + 3333
+    // payout was computed on the price minus the market fee
+    let mut remainder = price - market_fee;
+    for &value in payout.values() {
+        remainder = remainder
+            .checked_sub(value.0)
+            .ok_or(PayoutFailureReason::InvalidPayout)?;
+    }
+    if remainder == 0 || remainder == 1 {
+        Ok(payout)
+    } else {
+        Err(PayoutFailureReason::InvalidPayout)
+    }
+}
+
 impl Contract {
     pub(crate) fn assert_owner(&self) {
         assert_eq!(
@@ -15,27 +106,217 @@ impl Contract {
         );
     }
 
+    /// storage deposit not covering used bytes
+    pub(crate) fn storage_available(&self, account_id: &AccountId) -> Balance {
+        let total = self.storage_deposits.get(account_id).unwrap_or(0);
+        let used = Balance::from(self.storage_used.get(account_id).unwrap_or(0)) * env::storage_byte_cost();
+        total.saturating_sub(used)
+    }
+
+    pub(crate) fn internal_use_storage(&mut self, account_id: &AccountId, bytes: StorageUsage) {
+        let used = self.storage_used.get(account_id).unwrap_or(0) + bytes;
+        let total = self.storage_deposits.get(account_id).unwrap_or(0);
+        let required = Balance::from(used) * env::storage_byte_cost();
+        assert!(
+            total >= required,
+            "Insufficient storage paid: {}, {} required for {} bytes",
+            total, required, used
+        );
+        self.storage_used.insert(account_id, &used);
+    }
+
+    pub(crate) fn internal_free_storage(&mut self, account_id: &AccountId, bytes: StorageUsage) {
+        let used = self.storage_used.get(account_id).unwrap_or(0).saturating_sub(bytes);
+        if used == 0 {
+            self.storage_used.remove(account_id);
+        } else {
+            self.storage_used.insert(account_id, &used);
+        }
+    }
+
+    /// charge (or give back) the storage written since initial_storage_usage to account_id
+    pub(crate) fn internal_charge_storage(&mut self, account_id: &AccountId, initial_storage_usage: StorageUsage) {
+        let storage_usage = env::storage_usage();
+        if storage_usage > initial_storage_usage {
+            self.internal_use_storage(account_id, storage_usage - initial_storage_usage);
+        } else {
+            self.internal_free_storage(account_id, initial_storage_usage - storage_usage);
+        }
+    }
+
+    /// the sale owner pays for the sale with the bid storage reserved for it instead of the bids themselves,
+    /// so bids don't need a storage deposit and never charge the sale owner in the bidder's transaction
+    pub(crate) fn internal_charge_sale_storage(
+        &mut self,
+        owner_id: &AccountId,
+        old_sale: Option<&Sale>,
+        new_sale: Option<&Sale>,
+        initial_storage_usage: StorageUsage,
+    ) {
+        let bid_history_length = self.bid_history_length;
+        let bid_bytes = |sale: Option<&Sale>| {
+            sale.map(|sale| {
+                i128::from(bytes_reserved_for_bids(sale, bid_history_length)) - i128::from(bytes_for_bids(&sale.bids))
+            })
+            .unwrap_or(0)
+        };
+        let delta = i128::from(env::storage_usage()) - i128::from(initial_storage_usage) + bid_bytes(new_sale)
+            - bid_bytes(old_sale);
+        match delta.cmp(&0) {
+            Ordering::Greater => self.internal_use_storage(owner_id, delta as StorageUsage),
+            Ordering::Less => self.internal_free_storage(owner_id, (-delta) as StorageUsage),
+            Ordering::Equal => {}
+        }
+    }
+
     /// refund the last bid of each token type, don't update sale because it's already been removed
 
     pub(crate) fn refund_all_bids(
         &mut self,
-        bids: &Bids,
+        sale: &Sale,
     ) {
-        for (bid_ft, bid_vec) in bids {
+        for (bid_ft, bid_vec) in &sale.bids {
             let bid = &bid_vec[bid_vec.len()-1];
-            if bid_ft == "near" {
-                    Promise::new(bid.owner_id.clone()).transfer(u128::from(bid.price));
-            } else {
-                ext_contract::ft_transfer(
-                    bid.owner_id.clone(),
-                    bid.price,
-                    None,
-                    bid_ft,
-                    1,
-                    GAS_FOR_FT_TRANSFER,
-                );
+            self.internal_refund_bid(&sale.nft_contract_id, &sale.token_id, bid_ft, bid);
+        }
+    }
+
+    pub(crate) fn internal_refund_bid(
+        &self,
+        nft_contract_id: &AccountId,
+        token_id: &TokenId,
+        ft_token_id: &FungibleTokenId,
+        bid: &Bid,
+    ) {
+        transfer_token(&bid.owner_id, ft_token_id, bid.price.0);
+        MarketEventKind::BidRefunded(vec![BidLog {
+            bidder_id: bid.owner_id.clone(),
+            nft_contract_id: nft_contract_id.clone(),
+            token_id: token_id.clone(),
+            ft_token_id: ft_token_id.clone(),
+            price: bid.price,
+        }])
+        .emit();
+    }
+
+    /// market keeps its fee, NEAR stays in the contract balance and FTs in the market's FT account
+    pub(crate) fn internal_pay_out(
+        &mut self,
+        ft_token_id: &FungibleTokenId,
+        payout: Payout,
+        market_fee: Balance,
+    ) {
+        if market_fee > 0 {
+            self.internal_deposit_fees(ft_token_id, market_fee);
+        }
+
+        for (receiver_id, amount) in payout {
+            transfer_token(&receiver_id, ft_token_id, amount.0);
+        }
+    }
+
+    /// transfer a token to the buyer of an escrowed offer, resolve_offer_purchase pays the seller
+    /// or refunds the buyer
+    pub(crate) fn internal_escrow_purchase(&mut self, purchase: OfferPurchase, approval_id: u64) -> Promise {
+        let market_fee = U128(market_fee_for_price(self.market_fee_bps, purchase.price.0));
+        ext_contract::nft_transfer_payout(
+            purchase.buyer_id.clone(),
+            purchase.token_id.clone(),
+            approval_id,
+            "payout from market".to_string(),
+            U128(purchase.price.0 - market_fee.0),
+            10,
+            &purchase.nft_contract_id,
+            1,
+            GAS_FOR_NFT_TRANSFER,
+        )
+        .then(ext_self::resolve_offer_purchase(
+            purchase,
+            market_fee,
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_ROYALTIES,
+        ))
+    }
+
+    /// insert the sale and add it to the indexes for views, charged to the sale owner.
+    /// a token approved again replaces its sale, the old sale's bids are refunded
+    pub(crate) fn internal_add_sale(&mut self, sale: Sale) {
+        let contract_and_token_id = format!("{}{}{}", sale.nft_contract_id, DELIMETER, sale.token_id);
+        assert!(self.legacy_sales.get(&contract_and_token_id).is_none(), "Sale not migrated yet");
+        let mut old_sale = self.sales.get(&contract_and_token_id);
+        if let Some(listed) = &old_sale {
+            self.refund_all_bids(listed);
+            // indexed under another owner or token_type, remove it (and free its owner's storage) first
+            if listed.owner_id != sale.owner_id || listed.token_type != sale.token_type {
+                self.internal_remove_sale(listed.nft_contract_id.clone(), listed.token_id.clone());
+                old_sale = None;
             }
         }
+        let initial_storage_usage = env::storage_usage();
+        self.internal_insert_sale(&sale);
+        self.internal_charge_sale_storage(&sale.owner_id, old_sale.as_ref(), Some(&sale), initial_storage_usage);
+    }
+
+    /// writes the sale and its indexes, the caller charges the storage
+    pub(crate) fn internal_insert_sale(&mut self, sale: &Sale) {
+        let contract_and_token_id = format!("{}{}{}", sale.nft_contract_id, DELIMETER, sale.token_id);
+        self.sales.insert(&contract_and_token_id, sale);
+
+        let mut by_owner_id = self.by_owner_id.get(&sale.owner_id).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::ByOwnerIdInner {
+                    account_id_hash: hash_account_id(&sale.owner_id),
+                }
+                .try_to_vec()
+                .unwrap(),
+            )
+        });
+        by_owner_id.insert(&contract_and_token_id);
+        self.by_owner_id.insert(&sale.owner_id, &by_owner_id);
+
+        let mut by_nft_contract_id = self
+            .by_nft_contract_id
+            .get(&sale.nft_contract_id)
+            .unwrap_or_else(|| {
+                UnorderedSet::new(
+                    StorageKey::ByNFTContractIdInner {
+                        account_id_hash: hash_account_id(&sale.nft_contract_id),
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                )
+            });
+        by_nft_contract_id.insert(&sale.token_id);
+        self.by_nft_contract_id
+            .insert(&sale.nft_contract_id, &by_nft_contract_id);
+
+        if let Some(token_type) = &sale.token_type {
+            assert!(sale.token_id.contains(token_type), "TokenType should be substr of TokenId");
+            let mut by_nft_token_type = self
+                .by_nft_token_type
+                .get(token_type)
+                .unwrap_or_else(|| {
+                    UnorderedSet::new(
+                        StorageKey::ByNFTTokenTypeInner {
+                            token_type_hash: hash_account_id(token_type),
+                        }
+                        .try_to_vec()
+                        .unwrap(),
+                    )
+                });
+            by_nft_token_type.insert(&contract_and_token_id);
+            self.by_nft_token_type
+                .insert(token_type, &by_nft_token_type);
+        }
+    }
+
+    /// write back a changed sale, the owner is charged for what it grew or gets back what it shrank
+    pub(crate) fn internal_update_sale(&mut self, contract_and_token_id: &ContractAndTokenId, sale: &Sale) {
+        let old_sale = self.sales.get(contract_and_token_id).expect("No sale");
+        let initial_storage_usage = env::storage_usage();
+        self.sales.insert(contract_and_token_id, sale);
+        self.internal_charge_sale_storage(&sale.owner_id, Some(&old_sale), Some(sale), initial_storage_usage);
     }
 
     pub(crate) fn internal_remove_sale(
@@ -43,11 +324,27 @@ impl Contract {
         nft_contract_id: AccountId,
         token_id: TokenId,
     ) -> Sale {
+        let initial_storage_usage = env::storage_usage();
         let contract_and_token_id = format!("{}{}{}", &nft_contract_id, DELIMETER, token_id);
         let sale = self.sales.remove(&contract_and_token_id).expect("No sale");
 
+        MarketEventKind::SaleRemoved(vec![SaleRemovedLog {
+            owner_id: sale.owner_id.clone(),
+            nft_contract_id,
+            token_id,
+        }])
+        .emit();
+
+        self.internal_remove_sale_indexes(&contract_and_token_id, &sale);
+        self.internal_charge_sale_storage(&sale.owner_id, Some(&sale), None, initial_storage_usage);
+
+        sale
+    }
+
+    /// removes the sale from the by_owner_id, by_nft_contract_id and by_nft_token_type sets
+    pub(crate) fn internal_remove_sale_indexes(&mut self, contract_and_token_id: &ContractAndTokenId, sale: &Sale) {
         let mut by_owner_id = self.by_owner_id.get(&sale.owner_id).expect("No sale by_owner_id");
-        by_owner_id.remove(&contract_and_token_id);
+        by_owner_id.remove(contract_and_token_id);
         if by_owner_id.is_empty() {
             self.by_owner_id.remove(&sale.owner_id);
         } else {
@@ -56,27 +353,24 @@ impl Contract {
 
         let mut by_nft_contract_id = self
             .by_nft_contract_id
-            .get(&nft_contract_id)
+            .get(&sale.nft_contract_id)
             .expect("No sale by nft_contract_id");
-        by_nft_contract_id.remove(&token_id);
+        by_nft_contract_id.remove(&sale.token_id);
         if by_nft_contract_id.is_empty() {
-            self.by_nft_contract_id.remove(&nft_contract_id);
+            self.by_nft_contract_id.remove(&sale.nft_contract_id);
         } else {
             self.by_nft_contract_id
-                .insert(&nft_contract_id, &by_nft_contract_id);
+                .insert(&sale.nft_contract_id, &by_nft_contract_id);
         }
 
-        let token_type = sale.token_type.clone();
-        if let Some(token_type) = token_type {
-            let mut by_nft_token_type = self.by_nft_token_type.get(&token_type).expect("No sale by nft_token_type");
-            by_nft_token_type.remove(&contract_and_token_id);
+        if let Some(token_type) = &sale.token_type {
+            let mut by_nft_token_type = self.by_nft_token_type.get(token_type).expect("No sale by nft_token_type");
+            by_nft_token_type.remove(contract_and_token_id);
             if by_nft_token_type.is_empty() {
-                self.by_nft_token_type.remove(&token_type);
+                self.by_nft_token_type.remove(token_type);
             } else {
-                self.by_nft_token_type.insert(&token_type, &by_nft_token_type);
+                self.by_nft_token_type.insert(token_type, &by_nft_token_type);
             }
         }
-
-        sale
     }
 }
@@ -4,22 +4,34 @@ use near_sdk::json_types::{ValidAccountId, U128, U64};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{
     assert_one_yocto, env, ext_contract, near_bindgen, AccountId, Balance, Gas, PanicOnDefault,
-    Promise, PromiseOrValue, CryptoHash, BorshStorageKey,
+    Promise, PromiseOrValue, CryptoHash, BorshStorageKey, StorageUsage,
 };
-use std::cmp::min;
+use std::cmp::{max, min};
 use std::collections::HashMap;
 
+pub use crate::collection_offers::*;
+use crate::events::*;
 use crate::external::*;
+pub use crate::fees::*;
+pub use crate::ft_token_removal::*;
 use crate::internal::*;
+pub use crate::migrate::*;
 use crate::sale::*;
+pub use crate::token_offers::*;
 use near_sdk::env::STORAGE_PRICE_PER_BYTE;
 
+mod collection_offers;
+mod events;
 mod external;
+mod fees;
+mod ft_token_removal;
 mod ft_callbacks;
 mod internal;
+mod migrate;
 mod nft_callbacks;
 mod sale;
 mod sale_views;
+mod token_offers;
 
 near_sdk::setup_alloc!();
 
@@ -29,13 +41,20 @@ const GAS_FOR_FT_TRANSFER: Gas = 5_000_000_000_000;
 /// greedy max Tgas for resolve_purchase
 const GAS_FOR_ROYALTIES: Gas = 115_000_000_000_000;
 const GAS_FOR_NFT_TRANSFER: Gas = 15_000_000_000_000;
+const GAS_FOR_NFT_TOKEN: Gas = 10_000_000_000_000;
+const GAS_FOR_RESOLVE_PRUNE: Gas = 25_000_000_000_000;
+const GAS_FOR_RESOLVE_RELIST: Gas = 20_000_000_000_000;
+const GAS_FOR_RESOLVE_WITHDRAW_FEES: Gas = 10_000_000_000_000;
+const GAS_FOR_RESOLVE_FILL_OFFER: Gas = GAS_FOR_NFT_TRANSFER + GAS_FOR_ROYALTIES + 20_000_000_000_000;
 const BID_HISTORY_LENGTH_DEFAULT: u8 = 1;
 const NO_DEPOSIT: Balance = 0;
-const STORAGE_PER_SALE: u128 = 1000 * STORAGE_PRICE_PER_BYTE;
+/// minimum storage deposit, sales, bids and offers are charged by the bytes they use
+const STORAGE_MIN_DEPOSIT: u128 = 1000 * STORAGE_PRICE_PER_BYTE;
 static DELIMETER: &str = "||";
 
 pub type SaleConditions = HashMap<FungibleTokenId, U128>;
 pub type Bids = HashMap<FungibleTokenId, Vec<Bid>>;
+pub type DutchAuctions = HashMap<FungibleTokenId, DutchAuction>;
 pub type TokenId = String;
 pub type TokenType = Option<String>;
 pub type FungibleTokenId = AccountId;
@@ -49,17 +68,41 @@ pub struct StorageBalanceBounds {
     pub max: Option<U128>,
 }
 
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalance {
+    pub total: U128,
+    pub available: U128,
+}
+
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct Contract {
     pub owner_id: AccountId,
     pub sales: UnorderedMap<ContractAndTokenId, Sale>,
+    /// CUSTOM - version 1 sales left for migrate_sales, empty on new markets
+    pub legacy_sales: UnorderedMap<ContractAndTokenId, SaleV1>,
     pub by_owner_id: LookupMap<AccountId, UnorderedSet<ContractAndTokenId>>,
     pub by_nft_contract_id: LookupMap<AccountId, UnorderedSet<TokenId>>,
     pub by_nft_token_type: LookupMap<AccountId, UnorderedSet<ContractAndTokenId>>,
     pub ft_token_ids: UnorderedSet<AccountId>,
     pub storage_deposits: LookupMap<AccountId, Balance>,
+    /// CUSTOM - bytes each account's sales, bids and offers use, covered by its storage deposit
+    pub storage_used: LookupMap<AccountId, StorageUsage>,
     pub bid_history_length: u8,
+    /// CUSTOM - fee on every sale in basis points, collected per FT in the treasury
+    pub market_fee_bps: u32,
+    pub treasury: LookupMap<FungibleTokenId, Balance>,
+    /// CUSTOM - escrowed offers on any token of a token_type
+    pub collection_offers: UnorderedMap<u64, CollectionOffer>,
+    pub collection_offers_by_type: LookupMap<String, UnorderedSet<u64>>,
+    pub collection_offers_by_owner_id: LookupMap<AccountId, UnorderedSet<u64>>,
+    pub next_collection_offer_id: u64,
+    /// CUSTOM - escrowed offers on individual (unlisted) tokens
+    pub token_offers: LookupMap<ContractAndTokenId, TokenOffers>,
+    pub token_offers_by_owner_id: LookupMap<AccountId, UnorderedSet<ContractAndTokenId>>,
+    /// CUSTOM - FTs staged for removal with remove_ft_token_ids
+    pub ft_token_removals: UnorderedMap<FungibleTokenId, FtTokenRemoval>,
 }
 
 /// Helper structure to for keys of the persistent collections.
@@ -74,21 +117,47 @@ pub enum StorageKey {
     ByNFTTokenTypeInner { token_type_hash: CryptoHash },
     FTTokenIds,
     StorageDeposits,
+    StorageUsed,
+    Treasury,
+    CollectionOffers,
+    CollectionOffersByType,
+    CollectionOffersByTypeInner { token_type_hash: CryptoHash },
+    CollectionOffersByOwnerId,
+    CollectionOffersByOwnerIdInner { account_id_hash: CryptoHash },
+    TokenOffers,
+    TokenOffersByOwnerId,
+    TokenOffersByOwnerIdInner { account_id_hash: CryptoHash },
+    FtTokenRemovals,
+    LegacySales,
+    MigratedSales,
 }
 
 #[near_bindgen]
 impl Contract {
     #[init]
-    pub fn new(owner_id: ValidAccountId, ft_token_ids:Option<Vec<ValidAccountId>>, bid_history_length:Option<u8>) -> Self {
+    pub fn new(owner_id: ValidAccountId, ft_token_ids:Option<Vec<ValidAccountId>>, bid_history_length:Option<u8>, market_fee_bps: Option<u32>) -> Self {
+        let market_fee_bps = market_fee_bps.unwrap_or(0);
+        assert!(market_fee_bps <= MARKET_FEE_BPS_CAP, "Market fee limited to {} bps", MARKET_FEE_BPS_CAP);
         let mut this = Self {
             owner_id: owner_id.into(),
             sales: UnorderedMap::new(StorageKey::Sales),
+            legacy_sales: UnorderedMap::new(StorageKey::LegacySales),
             by_owner_id: LookupMap::new(StorageKey::ByOwnerId),
             by_nft_contract_id: LookupMap::new(StorageKey::ByNFTContractId),
             by_nft_token_type: LookupMap::new(StorageKey::ByNFTTokenType),
             ft_token_ids: UnorderedSet::new(StorageKey::FTTokenIds),
             storage_deposits: LookupMap::new(StorageKey::StorageDeposits),
+            storage_used: LookupMap::new(StorageKey::StorageUsed),
             bid_history_length: bid_history_length.unwrap_or(BID_HISTORY_LENGTH_DEFAULT),
+            market_fee_bps,
+            treasury: LookupMap::new(StorageKey::Treasury),
+            collection_offers: UnorderedMap::new(StorageKey::CollectionOffers),
+            collection_offers_by_type: LookupMap::new(StorageKey::CollectionOffersByType),
+            collection_offers_by_owner_id: LookupMap::new(StorageKey::CollectionOffersByOwnerId),
+            next_collection_offer_id: 0,
+            token_offers: LookupMap::new(StorageKey::TokenOffers),
+            token_offers_by_owner_id: LookupMap::new(StorageKey::TokenOffersByOwnerId),
+            ft_token_removals: UnorderedMap::new(StorageKey::FtTokenRemovals),
         };
         // support NEAR by default
         this.ft_token_ids.insert(&"near".to_string());
@@ -99,6 +168,7 @@ impl Contract {
             }
         }
 
+        write_contract_version();
         this
     }
 
@@ -107,44 +177,67 @@ impl Contract {
         self.assert_owner();
         let mut added = vec![];
         for ft_token_id in ft_token_ids {
+            // adding back a token cancels its removal if processing hasn't started
+            if let Some(removal) = self.ft_token_removals.get(ft_token_id.as_ref()) {
+                assert!(removal.next_sale_index.is_none(), "Removal of {} in progress", ft_token_id);
+                self.ft_token_removals.remove(ft_token_id.as_ref());
+            }
             added.push(self.ft_token_ids.insert(ft_token_id.as_ref()));
         }
         added
     }
 
-    /// TODO remove token (should check if sales can complete even if owner stops supporting token type)
+    /// remove_ft_token_ids and process_ft_token_removal in ft_token_removal.rs
 
+    /// NEP-145, registration_only keeps just the minimum deposit for new accounts (and nothing for registered ones)
     #[payable]
-    pub fn storage_deposit(&mut self, account_id: Option<ValidAccountId>) {
+    pub fn storage_deposit(
+        &mut self,
+        account_id: Option<ValidAccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
         let storage_account_id = account_id
             .map(|a| a.into())
             .unwrap_or_else(env::predecessor_account_id);
-        let deposit = env::attached_deposit();
-        assert!(
-            deposit >= STORAGE_PER_SALE,
-            "Requires minimum deposit of {}",
-            STORAGE_PER_SALE
-        );
-        let mut balance: u128 = self.storage_deposits.get(&storage_account_id).unwrap_or(0);
-        balance += deposit;
-        self.storage_deposits.insert(&storage_account_id, &balance);
+        let mut deposit = env::attached_deposit();
+        let balance = self.storage_deposits.get(&storage_account_id);
+        if balance.is_none() {
+            assert!(
+                deposit >= STORAGE_MIN_DEPOSIT,
+                "Requires minimum deposit of {}",
+                STORAGE_MIN_DEPOSIT
+            );
+        }
+        if registration_only.unwrap_or(false) {
+            let kept = if balance.is_none() { STORAGE_MIN_DEPOSIT } else { 0 };
+            if deposit > kept {
+                Promise::new(env::predecessor_account_id()).transfer(deposit - kept);
+            }
+            deposit = kept;
+        }
+        let total = balance.unwrap_or(0) + deposit;
+        self.storage_deposits.insert(&storage_account_id, &total);
+        self.internal_storage_balance(&storage_account_id)
     }
 
+    /// NEP-145, withdraws from the deposit not covering used bytes (all of it when amount is omitted)
     #[payable]
-    pub fn storage_withdraw(&mut self) {
+    pub fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
         assert_one_yocto();
         let owner_id = env::predecessor_account_id();
-        let mut amount = self.storage_deposits.remove(&owner_id).unwrap_or(0);
-        let sales = self.by_owner_id.get(&owner_id);
-        let len = sales.map(|s| s.len()).unwrap_or_default();
-        let diff = u128::from(len) * STORAGE_PER_SALE;
-        amount -= diff;
+        let total = self.storage_deposits.get(&owner_id).expect("No storage deposit");
+        let available = self.storage_available(&owner_id);
+        let amount = amount.map(|a| a.0).unwrap_or(available);
+        assert!(
+            amount <= available,
+            "Amount {} exceeds available storage balance {}",
+            amount, available
+        );
         if amount > 0 {
+            self.storage_deposits.insert(&owner_id, &(total - amount));
             Promise::new(owner_id.clone()).transfer(amount);
         }
-        if diff > 0 {
-            self.storage_deposits.insert(&owner_id, &diff);
-        }
+        self.internal_storage_balance(&owner_id)
     }
 
     /// views
@@ -155,17 +248,24 @@ impl Contract {
 
     pub fn storage_balance_bounds(&self) -> StorageBalanceBounds {
         StorageBalanceBounds {
-            min: U128(STORAGE_PER_SALE),
+            min: U128(STORAGE_MIN_DEPOSIT),
             max: None,
         }
     }
 
     pub fn storage_minimum_balance(&self) -> U128 {
-        U128(STORAGE_PER_SALE)
+        U128(STORAGE_MIN_DEPOSIT)
     }
 
-    pub fn storage_balance_of(&self, account_id: ValidAccountId) -> U128 {
-        U128(self.storage_deposits.get(account_id.as_ref()).unwrap_or(0))
+    pub fn storage_balance_of(&self, account_id: ValidAccountId) -> Option<StorageBalance> {
+        self.storage_deposits
+            .get(account_id.as_ref())
+            .map(|_| self.internal_storage_balance(account_id.as_ref()))
+    }
+
+    /// bytes used by the account's sales (with their reserved bid storage) and offers
+    pub fn storage_used_by(&self, account_id: ValidAccountId) -> U64 {
+        U64(self.storage_used.get(account_id.as_ref()).unwrap_or(0))
     }
 
     /// deprecated
@@ -175,6 +275,1000 @@ impl Contract {
     }
 
     pub fn storage_amount(&self) -> U128 {
-        U128(STORAGE_PER_SALE)
+        U128(STORAGE_MIN_DEPOSIT)
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_storage_balance(&self, account_id: &AccountId) -> StorageBalance {
+        StorageBalance {
+            total: U128(self.storage_deposits.get(account_id).unwrap_or(0)),
+            available: U128(self.storage_available(account_id)),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod market_tests {
+    use near_sdk::MockedBlockchain;
+    use near_sdk::test_utils::{accounts, get_created_receipts, get_logs, testing_env_with_promise_results, VMContextBuilder};
+    use near_sdk::{testing_env, PromiseResult};
+    use std::convert::TryFrom;
+
+    use super::*;
+    use crate::ft_callbacks::FungibleTokenReceiver;
+    use crate::nft_callbacks::NonFungibleTokenApprovalsReceiver;
+
+    const ONE_NEAR: Balance = 1_000_000_000_000_000_000_000_000;
+
+    fn market() -> ValidAccountId {
+        ValidAccountId::try_from("market.near").unwrap()
+    }
+    fn nft() -> ValidAccountId {
+        ValidAccountId::try_from("nft.near").unwrap()
+    }
+    fn ft() -> ValidAccountId {
+        ValidAccountId::try_from("ft.near").unwrap()
+    }
+
+    fn get_context(predecessor_account_id: ValidAccountId) -> VMContextBuilder {
+        let mut builder = VMContextBuilder::new();
+        builder
+            .current_account_id(market())
+            .account_balance(1000 * ONE_NEAR)
+            .signer_account_id(predecessor_account_id.clone())
+            .predecessor_account_id(predecessor_account_id);
+        builder
+    }
+
+    /// private callbacks are called by the market itself
+    fn self_context() -> VMContextBuilder {
+        get_context(market())
+    }
+
+    fn setup_contract() -> Contract {
+        testing_env!(get_context(accounts(0)).build());
+        let mut contract = Contract::new(accounts(0), Some(vec![ft()]), None, None);
+        for account_id in 1..4 {
+            testing_env!(get_context(accounts(account_id)).attached_deposit(ONE_NEAR).build());
+            contract.storage_deposit(None, None);
+        }
+        contract
+    }
+
+    /// seller accounts(1) approves the market on the NFT contract
+    fn list(contract: &mut Contract, token_id: &str, msg: &str) {
+        testing_env!(get_context(nft())
+            .signer_account_id(accounts(1))
+            .block_timestamp(0)
+            .build());
+        contract.nft_on_approve(token_id.to_string(), accounts(1), 1, msg.to_string());
+    }
+
+    fn contract_and_token_id(token_id: &str) -> ContractAndTokenId {
+        format!("{}{}{}", nft(), DELIMETER, token_id)
+    }
+
+    fn offer_near(contract: &mut Contract, buyer_id: ValidAccountId, token_id: &str, amount: Balance) {
+        testing_env!(get_context(buyer_id).attached_deposit(amount).build());
+        contract.offer(nft(), token_id.to_string());
+    }
+
+    fn offer_ft(contract: &mut Contract, buyer_id: ValidAccountId, token_id: &str, amount: Balance) -> PromiseOrValue<U128> {
+        testing_env!(get_context(ft()).build());
+        let msg = near_sdk::serde_json::json!({ "nft_contract_id": nft(), "token_id": token_id }).to_string();
+        contract.ft_on_transfer(buyer_id.into(), U128(amount), msg)
+    }
+
+    /// what the NFT contract's nft_token returns, seller owns the token and the market has approval 1
+    fn token_result(owner_id: ValidAccountId, approval_id: u64) -> PromiseResult {
+        let token = near_sdk::serde_json::json!({
+            "owner_id": owner_id,
+            "approved_account_ids": { market().to_string(): approval_id },
+        });
+        PromiseResult::Successful(token.to_string().into_bytes())
+    }
+
+    #[derive(Deserialize)]
+    #[serde(crate = "near_sdk::serde")]
+    struct ReceiptView {
+        receiver_id: AccountId,
+        actions: Vec<ActionView>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(crate = "near_sdk::serde")]
+    enum ActionView {
+        FunctionCall { method_name: String, args: String },
+        Transfer { deposit: Balance },
+    }
+
+    fn receipts() -> Vec<ReceiptView> {
+        let receipts = near_sdk::serde_json::to_string(&get_created_receipts()).unwrap();
+        near_sdk::serde_json::from_str(&receipts).unwrap()
+    }
+
+    /// NEAR sent by the market in the current call
+    fn near_transfers() -> Vec<(AccountId, Balance)> {
+        receipts()
+            .into_iter()
+            .flat_map(|receipt| {
+                let receiver_id = receipt.receiver_id;
+                receipt.actions.into_iter().filter_map(move |action| match action {
+                    ActionView::Transfer { deposit } => Some((receiver_id.clone(), deposit)),
+                    _ => None,
+                })
+            })
+            .collect()
+    }
+
+    /// args of every call to method_name as (contract, args)
+    fn calls(method: &str) -> Vec<(AccountId, near_sdk::serde_json::Value)> {
+        receipts()
+            .into_iter()
+            .flat_map(|receipt| {
+                let receiver_id = receipt.receiver_id;
+                receipt.actions.into_iter().filter_map(move |action| match action {
+                    ActionView::FunctionCall { method_name, args } if method_name == method => {
+                        Some((receiver_id.clone(), near_sdk::serde_json::from_str(&args).unwrap()))
+                    }
+                    _ => None,
+                })
+            })
+            .collect()
+    }
+
+    /// FTs sent by the market in the current call as (ft_token_id, receiver_id, amount)
+    fn ft_transfers() -> Vec<(AccountId, AccountId, Balance)> {
+        calls("ft_transfer")
+            .into_iter()
+            .map(|(ft_token_id, args)| {
+                (
+                    ft_token_id,
+                    args["receiver_id"].as_str().unwrap().to_string(),
+                    args["amount"].as_str().unwrap().parse().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    fn emitted(event: &str) -> bool {
+        get_logs().iter().any(|log| log.contains(&format!("\"event\":\"{}\"", event)))
+    }
+
+    /// runs the resolve_purchase queued by process_purchase with the NFT contract's result
+    fn resolve_queued_purchase(contract: &mut Contract, result: PromiseResult) -> U128 {
+        let (_, args) = calls("resolve_purchase").pop().expect("No purchase");
+        // the callback runs at the time of the purchase
+        testing_env_with_promise_results(self_context().block_timestamp(env::block_timestamp()).build(), result);
+        contract.resolve_purchase(
+            near_sdk::serde_json::from_value(args["sale"].clone()).unwrap(),
+            near_sdk::serde_json::from_value(args["purchase"].clone()).unwrap(),
+            near_sdk::serde_json::from_value(args["market_fee"].clone()).unwrap(),
+        )
+    }
+
+    fn revoke(contract: &mut Contract, token_id: &str, owner_id: ValidAccountId, approval_id: u64) {
+        testing_env!(get_context(nft()).build());
+        contract.nft_on_revoke(token_id.to_string(), owner_id, approval_id);
+    }
+
+    #[test]
+    fn revoke_removes_sale_and_refunds_bids() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", r#"{"sale_conditions": {"near": "1000"}, "is_auction": true}"#);
+        offer_near(&mut contract, accounts(2), "1", 2000);
+
+        revoke(&mut contract, "1", accounts(1), 1);
+        assert!(contract.get_sale(contract_and_token_id("1")).is_none());
+        assert_eq!(near_transfers(), vec![(accounts(2).to_string(), 2000)]);
+    }
+
+    #[test]
+    fn revoke_of_stale_approval_keeps_sale() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", r#"{"sale_conditions": {"near": "1000"}}"#);
+
+        revoke(&mut contract, "1", accounts(1), 0);
+        assert!(contract.get_sale(contract_and_token_id("1")).is_some());
+    }
+
+    #[test]
+    fn revoke_by_other_owner_keeps_sale() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", r#"{"sale_conditions": {"near": "1000"}}"#);
+
+        revoke(&mut contract, "1", accounts(2), 1);
+        assert!(contract.get_sale(contract_and_token_id("1")).is_some());
+    }
+
+    #[test]
+    fn offer_refunds_outbid_bid() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", r#"{"sale_conditions": {"near": "1000"}, "is_auction": true}"#);
+        offer_near(&mut contract, accounts(2), "1", 2000);
+        offer_near(&mut contract, accounts(3), "1", 3000);
+
+        assert_eq!(near_transfers(), vec![(accounts(2).to_string(), 2000)]);
+        let bids = &contract.get_sale(contract_and_token_id("1")).unwrap().bids["near"];
+        assert_eq!(bids[bids.len() - 1].owner_id, accounts(3).to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't pay less than or equal to current bid price")]
+    fn offer_below_current_bid() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", r#"{"sale_conditions": {"near": "1000"}, "is_auction": true}"#);
+        offer_near(&mut contract, accounts(2), "1", 2000);
+        offer_near(&mut contract, accounts(3), "1", 2000);
+    }
+
+    #[test]
+    fn approving_listed_token_again_replaces_sale() {
+        let mut contract = setup_contract();
+        let msg = r#"{"sale_conditions": {"near": "1000"}, "is_auction": true}"#;
+        list(&mut contract, "1", msg);
+        offer_near(&mut contract, accounts(2), "1", 2000);
+        let storage_used = contract.storage_used_by(accounts(1)).0;
+
+        list(&mut contract, "1", msg);
+        assert_eq!(near_transfers(), vec![(accounts(2).to_string(), 2000)]);
+        assert!(contract.get_sale(contract_and_token_id("1")).unwrap().bids.is_empty());
+        assert_eq!(contract.storage_used_by(accounts(1)).0, storage_used);
+        assert_eq!(contract.get_supply_by_owner_id(accounts(1).into()).0, 1);
+    }
+
+    #[test]
+    fn remove_sales_batch_refunds_bids() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", r#"{"sale_conditions": {"near": "1000"}, "is_auction": true}"#);
+        offer_near(&mut contract, accounts(2), "1", 2000);
+
+        testing_env!(get_context(accounts(0)).attached_deposit(1).build());
+        let removed = contract.remove_sales_batch(vec![contract_and_token_id("1"), contract_and_token_id("2")]);
+
+        assert_eq!(removed, vec![true, false]);
+        assert!(contract.get_sale(contract_and_token_id("1")).is_none());
+        assert_eq!(near_transfers(), vec![(accounts(2).to_string(), 2000)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Owner's method")]
+    fn remove_sales_batch_requires_owner() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", r#"{"sale_conditions": {"near": "1000"}}"#);
+
+        testing_env!(get_context(accounts(2)).attached_deposit(1).build());
+        contract.remove_sales_batch(vec![contract_and_token_id("1")]);
+    }
+
+    #[test]
+    fn prune_sale_removes_sale_of_moved_token() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", r#"{"sale_conditions": {"ft.near": "1000"}, "is_auction": true}"#);
+        offer_ft(&mut contract, accounts(2), "1", 2000);
+
+        testing_env_with_promise_results(self_context().build(), token_result(accounts(3), 1));
+        assert!(contract.resolve_prune_sale(nft().into(), "1".to_string()));
+
+        assert!(contract.get_sale(contract_and_token_id("1")).is_none());
+        assert_eq!(ft_transfers(), vec![(ft().to_string(), accounts(2).to_string(), 2000)]);
+    }
+
+    #[test]
+    fn prune_sale_keeps_valid_sale() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", r#"{"sale_conditions": {"near": "1000"}}"#);
+
+        testing_env_with_promise_results(self_context().build(), token_result(accounts(1), 1));
+        assert!(!contract.resolve_prune_sale(nft().into(), "1".to_string()));
+
+        // a failed nft_token call can't tell whether the sale is stale
+        testing_env_with_promise_results(self_context().build(), PromiseResult::Failed);
+        assert!(!contract.resolve_prune_sale(nft().into(), "1".to_string()));
+
+        assert!(contract.get_sale(contract_and_token_id("1")).is_some());
+    }
+
+    #[test]
+    fn prune_sale_removes_sale_with_changed_approval() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", r#"{"sale_conditions": {"near": "1000"}}"#);
+
+        testing_env_with_promise_results(self_context().build(), token_result(accounts(1), 2));
+        assert!(contract.resolve_prune_sale(nft().into(), "1".to_string()));
+        assert!(contract.get_sale(contract_and_token_id("1")).is_none());
+    }
+
+    #[test]
+    fn failed_payout_refunds_accepted_ft_bid() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", r#"{"sale_conditions": {"ft.near": "1000", "near": "1000"}, "is_auction": true}"#);
+        offer_ft(&mut contract, accounts(2), "1", 2000);
+        offer_near(&mut contract, accounts(3), "1", 1500);
+
+        testing_env!(get_context(accounts(1)).build());
+        contract.accept_offer(nft(), "1".to_string(), ft());
+        assert_eq!(calls("resolve_purchase")[0].1["purchase"]["escrowed"], true);
+
+        resolve_queued_purchase(&mut contract, PromiseResult::Failed);
+        assert_eq!(ft_transfers(), vec![(ft().to_string(), accounts(2).to_string(), 2000)]);
+        assert_eq!(near_transfers(), vec![(accounts(3).to_string(), 1500)]);
+        assert!(emitted("payout_failed"));
+        // the transfer failed, relisted if the seller still owns the token
+        assert_eq!(calls("nft_token").len(), 1);
+    }
+
+    #[test]
+    fn failed_payout_returns_ft_purchase_through_resolve_transfer() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", r#"{"sale_conditions": {"ft.near": "1000"}}"#);
+        offer_ft(&mut contract, accounts(2), "1", 1000);
+        assert_eq!(calls("resolve_purchase")[0].1["purchase"]["escrowed"], false);
+
+        let unused = resolve_queued_purchase(&mut contract, PromiseResult::Failed);
+        assert_eq!(unused.0, 1000);
+        assert!(ft_transfers().is_empty());
+    }
+
+    #[test]
+    fn invalid_payout_refunds_near_without_relisting() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", r#"{"sale_conditions": {"near": "1000"}}"#);
+        offer_near(&mut contract, accounts(2), "1", 1000);
+
+        let payout = near_sdk::serde_json::json!({ accounts(1).to_string(): "2000" }).to_string();
+        resolve_queued_purchase(&mut contract, PromiseResult::Successful(payout.into_bytes()));
+        assert_eq!(near_transfers(), vec![(accounts(2).to_string(), 1000)]);
+        assert!(calls("nft_token").is_empty());
+        assert!(emitted("payout_failed"));
+    }
+
+    const TIMED_AUCTION: &str = r#"{"sale_conditions": {"ft.near": "1000"}, "is_auction": true, "ends_at": "10000",
+        "min_bid_increment": {"basis_points": 1000}, "extension_window": "1000"}"#;
+
+    fn ms(ms: u64) -> u64 {
+        ms * 1_000_000
+    }
+
+    #[test]
+    fn timed_auction_extends_late_bids_and_refunds_outbid() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", TIMED_AUCTION);
+        offer_ft(&mut contract, accounts(2), "1", 1000);
+
+        testing_env!(get_context(ft()).block_timestamp(ms(9_500)).build());
+        let msg = near_sdk::serde_json::json!({ "nft_contract_id": nft(), "token_id": "1" }).to_string();
+        contract.ft_on_transfer(accounts(3).into(), U128(1100), msg);
+
+        assert_eq!(ft_transfers(), vec![(ft().to_string(), accounts(2).to_string(), 1000)]);
+        let sale = contract.get_sale(contract_and_token_id("1")).unwrap();
+        assert_eq!(sale.ends_at.unwrap().0, 10_500);
+        assert_eq!(sale.bids["ft.near"].last().unwrap().owner_id, accounts(3).to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Bid must be at least 1100")]
+    fn timed_auction_min_bid_increment() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", TIMED_AUCTION);
+        offer_ft(&mut contract, accounts(2), "1", 1000);
+        offer_ft(&mut contract, accounts(3), "1", 1099);
+    }
+
+    #[test]
+    #[should_panic(expected = "Auction has ended")]
+    fn timed_auction_rejects_late_bid() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", TIMED_AUCTION);
+
+        testing_env!(get_context(ft()).block_timestamp(ms(10_000)).build());
+        let msg = near_sdk::serde_json::json!({ "nft_contract_id": nft(), "token_id": "1" }).to_string();
+        contract.ft_on_transfer(accounts(2).into(), U128(1000), msg);
+    }
+
+    #[test]
+    #[should_panic(expected = "Auction has not ended")]
+    fn settle_auction_before_end() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", TIMED_AUCTION);
+        offer_ft(&mut contract, accounts(2), "1", 1000);
+
+        testing_env!(get_context(accounts(3)).block_timestamp(ms(9_999)).build());
+        contract.settle_auction(nft(), "1".to_string());
+    }
+
+    #[test]
+    fn settle_auction_sells_to_highest_bid() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", TIMED_AUCTION);
+        offer_ft(&mut contract, accounts(2), "1", 1000);
+        offer_ft(&mut contract, accounts(3), "1", 1500);
+
+        testing_env!(get_context(accounts(0)).block_timestamp(ms(10_000)).build());
+        contract.settle_auction(nft(), "1".to_string());
+
+        assert!(contract.get_sale(contract_and_token_id("1")).is_none());
+        let (_, transfer) = calls("nft_transfer_payout").pop().unwrap();
+        assert_eq!(transfer["receiver_id"], accounts(3).to_string());
+        assert_eq!(transfer["balance"], "1500");
+
+        // the winning bid is held by the market, a failed transfer refunds it
+        resolve_queued_purchase(&mut contract, PromiseResult::Failed);
+        assert_eq!(ft_transfers(), vec![(ft().to_string(), accounts(3).to_string(), 1500)]);
+        // the auction has ended, it isn't listed again
+        assert!(calls("nft_token").is_empty());
+    }
+
+    #[test]
+    fn settle_auction_without_bids_removes_sale() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", TIMED_AUCTION);
+
+        testing_env!(get_context(accounts(0)).block_timestamp(ms(10_000)).build());
+        contract.settle_auction(nft(), "1".to_string());
+
+        assert!(contract.get_sale(contract_and_token_id("1")).is_none());
+        assert!(calls("nft_transfer_payout").is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Timed auctions are settled with settle_auction")]
+    fn timed_auction_cannot_accept_offer() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", TIMED_AUCTION);
+        offer_ft(&mut contract, accounts(2), "1", 1000);
+
+        testing_env!(get_context(accounts(1)).build());
+        contract.accept_offer(nft(), "1".to_string(), ft());
+    }
+
+    const DUTCH_AUCTION: &str = r#"{"sale_conditions": {}, "dutch_auction": {
+        "near": {"start_price": "10000", "floor_price": "2000", "starts_at": "0", "decay": {"linear": {"duration": "1000"}}},
+        "ft.near": {"start_price": "10000", "floor_price": "2000", "starts_at": "0",
+            "decay": {"stepwise": {"step_interval": "100", "step_amount": "1000"}}}
+    }}"#;
+
+    #[test]
+    fn dutch_auction_price_follows_schedule() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", DUTCH_AUCTION);
+
+        let price_at = |timestamp_ms: u64, ft_token_id: &str| {
+            testing_env!(get_context(accounts(2)).block_timestamp(ms(timestamp_ms)).build());
+            contract.get_current_price(nft().into(), "1".to_string(), ft_token_id.to_string()).0
+        };
+        assert_eq!(price_at(0, "near"), 10000);
+        assert_eq!(price_at(500, "near"), 6000);
+        assert_eq!(price_at(2000, "near"), 2000);
+        assert_eq!(price_at(250, "ft.near"), 8000);
+        assert_eq!(price_at(5000, "ft.near"), 2000);
+    }
+
+    #[test]
+    fn dutch_auction_refunds_near_overpayment() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", DUTCH_AUCTION);
+
+        testing_env!(get_context(accounts(2)).attached_deposit(8000).block_timestamp(ms(500)).build());
+        contract.offer(nft(), "1".to_string());
+
+        assert_eq!(near_transfers(), vec![(accounts(2).to_string(), 2000)]);
+        assert_eq!(calls("nft_transfer_payout")[0].1["balance"], "6000");
+        assert_eq!(calls("resolve_purchase")[0].1["purchase"]["price"], "6000");
+    }
+
+    #[test]
+    fn dutch_auction_returns_ft_overpayment() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", DUTCH_AUCTION);
+
+        testing_env!(get_context(ft()).block_timestamp(ms(250)).build());
+        let msg = near_sdk::serde_json::json!({ "nft_contract_id": nft(), "token_id": "1" }).to_string();
+        contract.ft_on_transfer(accounts(2).into(), U128(9000), msg);
+
+        assert_eq!(ft_transfers(), vec![(ft().to_string(), accounts(2).to_string(), 1000)]);
+        assert_eq!(calls("resolve_purchase")[0].1["purchase"]["price"], "8000");
+    }
+
+    #[test]
+    #[should_panic(expected = "Attached deposit must be at least the current price: 6000")]
+    fn dutch_auction_below_current_price() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", DUTCH_AUCTION);
+
+        testing_env!(get_context(accounts(2)).attached_deposit(5999).block_timestamp(ms(500)).build());
+        contract.offer(nft(), "1".to_string());
+    }
+
+    const LOCKED_AUCTION: &str = r#"{"sale_conditions": {"ft.near": "1000"}, "is_auction": true, "ends_at": "10000",
+        "bid_lock_window": "2000"}"#;
+
+    fn cancel_bid(contract: &mut Contract, bidder_id: ValidAccountId, ft_token_id: &str, now: u64) {
+        testing_env!(get_context(bidder_id).attached_deposit(1).block_timestamp(now).build());
+        contract.cancel_bid(nft(), "1".to_string(), ValidAccountId::try_from(ft_token_id).unwrap());
+    }
+
+    #[test]
+    fn cancel_bid_refunds_near_bid() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", r#"{"sale_conditions": {"near": "1000"}, "is_auction": true}"#);
+        offer_near(&mut contract, accounts(2), "1", 5000);
+
+        cancel_bid(&mut contract, accounts(2), "near", 0);
+        assert_eq!(near_transfers(), vec![(accounts(2).to_string(), 5000)]);
+        assert!(contract.get_sale(contract_and_token_id("1")).unwrap().bids.get("near").is_none());
+    }
+
+    #[test]
+    fn cancel_bid_refunds_ft_bid_before_lock_window() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", LOCKED_AUCTION);
+        offer_ft(&mut contract, accounts(2), "1", 1000);
+
+        cancel_bid(&mut contract, accounts(2), "ft.near", ms(7_999));
+        assert_eq!(ft_transfers(), vec![(ft().to_string(), accounts(2).to_string(), 1000)]);
+        assert!(contract.get_sale(contract_and_token_id("1")).unwrap().bids.get("ft.near").is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Bids can't be cancelled at the end of the auction")]
+    fn cancel_bid_in_lock_window() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", LOCKED_AUCTION);
+        offer_ft(&mut contract, accounts(2), "1", 1000);
+
+        cancel_bid(&mut contract, accounts(2), "ft.near", ms(8_000));
+    }
+
+    #[test]
+    #[should_panic(expected = "Must be current bid owner")]
+    fn cancel_bid_requires_bid_owner() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", r#"{"sale_conditions": {"near": "1000"}, "is_auction": true}"#);
+        offer_near(&mut contract, accounts(2), "1", 5000);
+
+        cancel_bid(&mut contract, accounts(3), "near", 0);
+    }
+
+    fn typed_token_result(owner_id: ValidAccountId, token_type: Option<&str>) -> PromiseResult {
+        let token = near_sdk::serde_json::json!({
+            "owner_id": owner_id,
+            "approved_account_ids": { market().to_string(): 1 },
+            "token_type": token_type,
+        });
+        PromiseResult::Successful(token.to_string().into_bytes())
+    }
+
+    /// accounts(2) offers 1000 NEAR each for 2 tokens of type A
+    fn setup_collection_offer() -> Contract {
+        let mut contract = setup_contract();
+        testing_env!(get_context(accounts(2)).attached_deposit(2000).build());
+        contract.make_collection_offer(nft(), "A".to_string(), U128(1000), 2);
+        contract
+    }
+
+    /// msg is what accounts(1) passes to nft_approve plus the token_type added by the NFT contract
+    fn fill_collection_offer(contract: &mut Contract, token_id: &str, msg: &str, token: PromiseResult) -> bool {
+        testing_env!(get_context(nft()).signer_account_id(accounts(1)).build());
+        contract.nft_on_approve(token_id.to_string(), accounts(1), 1, msg.to_string());
+        assert_eq!(calls("nft_token")[0].1["token_id"], token_id);
+
+        let (_, args) = calls("resolve_fill_collection_offer").pop().expect("No fill");
+        testing_env_with_promise_results(self_context().build(), token);
+        contract.resolve_fill_collection_offer(near_sdk::serde_json::from_value(args["fill_offer"].clone()).unwrap())
+    }
+
+    #[test]
+    fn fill_collection_offer_checks_token_type_on_nft_contract() {
+        let mut contract = setup_collection_offer();
+        let token = typed_token_result(accounts(1), Some("A"));
+        assert!(fill_collection_offer(&mut contract, "A:1", r#"{"fill_offer":"0","token_type":"A"}"#, token));
+
+        let (_, args) = calls("nft_transfer_payout").pop().unwrap();
+        assert_eq!(args["receiver_id"], accounts(2).to_string());
+        assert_eq!(args["balance"], "1000");
+        assert_eq!(contract.get_collection_offer(U64(0)).unwrap().quantity, 1);
+
+        let token = typed_token_result(accounts(1), Some("A"));
+        assert!(fill_collection_offer(&mut contract, "A:2", r#"{"fill_offer":"0","token_type":"A"}"#, token));
+        assert!(contract.get_collection_offer(U64(0)).is_none());
+    }
+
+    #[test]
+    fn fill_collection_offer_rejects_spoofed_token_type() {
+        let mut contract = setup_collection_offer();
+        // an untyped token gets no token_type from the NFT contract, the owner wrote it into the msg
+        let token = typed_token_result(accounts(1), None);
+        assert!(!fill_collection_offer(&mut contract, "1", r#"{"fill_offer":"0","token_type":"A"}"#, token));
+        let token = typed_token_result(accounts(1), Some("B"));
+        assert!(!fill_collection_offer(&mut contract, "B:1", r#"{"fill_offer":"0","token_type":"A"}"#, token));
+
+        assert!(calls("nft_transfer_payout").is_empty());
+        assert_eq!(contract.get_collection_offer(U64(0)).unwrap().quantity, 2);
+    }
+
+    #[test]
+    fn fill_collection_offer_rejects_token_of_another_owner() {
+        let mut contract = setup_collection_offer();
+        let token = typed_token_result(accounts(3), Some("A"));
+        assert!(!fill_collection_offer(&mut contract, "A:1", r#"{"fill_offer":"0","token_type":"A"}"#, token));
+        assert!(calls("nft_transfer_payout").is_empty());
+        assert_eq!(contract.get_collection_offer(U64(0)).unwrap().quantity, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token is not of the offer's token_type")]
+    fn fill_collection_offer_with_other_token_type() {
+        let mut contract = setup_collection_offer();
+        testing_env!(get_context(nft()).signer_account_id(accounts(1)).build());
+        contract.nft_on_approve("B:1".to_string(), accounts(1), 1, r#"{"fill_offer":"0","token_type":"B"}"#.to_string());
+    }
+
+    fn make_token_offer(contract: &mut Contract, owner_id: ValidAccountId, amount: Balance, expires_at: u64) {
+        testing_env!(get_context(owner_id).attached_deposit(amount).build());
+        contract.make_token_offer(nft(), "7".to_string(), U64(expires_at));
+    }
+
+    fn accept_token_offer(contract: &mut Contract, offerer_id: ValidAccountId, now: u64) {
+        testing_env!(get_context(nft()).signer_account_id(accounts(1)).block_timestamp(now).build());
+        let msg = near_sdk::serde_json::json!({ "accept_offer": offerer_id }).to_string();
+        contract.nft_on_approve("7".to_string(), accounts(1), 1, msg);
+    }
+
+    #[test]
+    fn cancel_token_offer_refunds_escrow() {
+        let mut contract = setup_contract();
+        make_token_offer(&mut contract, accounts(2), 3000, 10_000);
+        assert_eq!(contract.get_token_offers(nft().into(), "7".to_string()).len(), 1);
+        assert_eq!(contract.get_supply_token_offers_by_owner_id(accounts(2).into()).0, 1);
+
+        testing_env!(get_context(accounts(2)).attached_deposit(1).build());
+        contract.cancel_token_offer(nft(), "7".to_string());
+        assert_eq!(near_transfers(), vec![(accounts(2).to_string(), 3000)]);
+        assert!(contract.get_token_offers(nft().into(), "7".to_string()).is_empty());
+        assert_eq!(contract.get_supply_token_offers_by_owner_id(accounts(2).into()).0, 0);
+    }
+
+    #[test]
+    fn refund_expired_token_offers_keeps_live_offers() {
+        let mut contract = setup_contract();
+        make_token_offer(&mut contract, accounts(2), 3000, 20_000);
+        testing_env!(get_context(ft()).build());
+        let msg = near_sdk::serde_json::json!({
+            "token_offer": { "nft_contract_id": nft(), "token_id": "7", "expires_at": "10000" }
+        });
+        let unused = contract.ft_on_transfer(accounts(3).into(), U128(500), msg.to_string());
+        assert!(matches!(unused, PromiseOrValue::Value(U128(0))));
+
+        testing_env!(get_context(accounts(4)).block_timestamp(ms(10_000)).build());
+        assert_eq!(contract.refund_expired_token_offers(nft(), "7".to_string()), 1);
+        assert_eq!(ft_transfers(), vec![(ft().to_string(), accounts(3).to_string(), 500)]);
+        let offers = contract.get_token_offers(nft().into(), "7".to_string());
+        assert_eq!(offers.len(), 1);
+        assert_eq!(offers[0].owner_id, accounts(2).to_string());
+    }
+
+    #[test]
+    fn accept_token_offer_escrows_and_refunds_failed_payout() {
+        let mut contract = setup_contract();
+        make_token_offer(&mut contract, accounts(2), 3000, 10_000);
+
+        accept_token_offer(&mut contract, accounts(2), ms(9_999));
+        let (_, args) = calls("nft_transfer_payout").pop().unwrap();
+        assert_eq!(args["receiver_id"], accounts(2).to_string());
+        assert_eq!(args["balance"], "3000");
+        assert!(contract.get_token_offers(nft().into(), "7".to_string()).is_empty());
+
+        let (_, args) = calls("resolve_offer_purchase").pop().unwrap();
+        testing_env_with_promise_results(self_context().build(), PromiseResult::Failed);
+        assert!(!contract.resolve_offer_purchase(
+            near_sdk::serde_json::from_value(args["purchase"].clone()).unwrap(),
+            near_sdk::serde_json::from_value(args["market_fee"].clone()).unwrap(),
+        ));
+        assert_eq!(near_transfers(), vec![(accounts(2).to_string(), 3000)]);
+        assert!(emitted("payout_failed"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Offer expired")]
+    fn accept_expired_token_offer() {
+        let mut contract = setup_contract();
+        make_token_offer(&mut contract, accounts(2), 3000, 10_000);
+        accept_token_offer(&mut contract, accounts(2), ms(10_000));
+    }
+
+    #[test]
+    #[should_panic(expected = "Token is listed, use offer or ft_transfer_call on the sale")]
+    fn token_offer_on_listed_token() {
+        let mut contract = setup_contract();
+        list(&mut contract, "7", r#"{"sale_conditions": {"near": "10000"}}"#);
+        make_token_offer(&mut contract, accounts(2), 3000, 10_000);
+    }
+
+    fn remove_ft(contract: &mut Contract, grace_period: u64) {
+        testing_env!(get_context(accounts(0)).build());
+        contract.remove_ft_token_ids(vec![ft()], Some(U64(grace_period)));
+    }
+
+    fn process_ft_removal(contract: &mut Contract, now: u64, limit: u64) -> bool {
+        testing_env!(get_context(accounts(4)).block_timestamp(now).build());
+        contract.process_ft_token_removal(ft(), limit)
+    }
+
+    #[test]
+    #[should_panic(expected = "Token ft.near not supported by this market")]
+    fn ft_pending_removal_blocks_new_bids() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", r#"{"sale_conditions": {"near": "10000", "ft.near": "1000"}, "is_auction": true}"#);
+        remove_ft(&mut contract, 1000);
+        offer_ft(&mut contract, accounts(2), "1", 1500);
+    }
+
+    #[test]
+    fn ft_pending_removal_still_sells_at_listed_price() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", r#"{"sale_conditions": {"ft.near": "1000"}}"#);
+        remove_ft(&mut contract, 1000);
+        offer_ft(&mut contract, accounts(2), "1", 1000);
+        assert_eq!(calls("nft_transfer_payout")[0].1["receiver_id"], accounts(2).to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Grace period has not ended")]
+    fn process_ft_removal_during_grace_period() {
+        let mut contract = setup_contract();
+        remove_ft(&mut contract, 1000);
+        process_ft_removal(&mut contract, ms(999), 10);
+    }
+
+    #[test]
+    fn process_ft_removal_strips_ft_and_refunds_bids() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", r#"{"sale_conditions": {"near": "10000", "ft.near": "1000"}, "is_auction": true}"#);
+        offer_ft(&mut contract, accounts(2), "1", 1500);
+        list(&mut contract, "2", r#"{"sale_conditions": {"ft.near": "1000"}, "is_auction": true}"#);
+        offer_ft(&mut contract, accounts(3), "2", 1200);
+        list(&mut contract, "3", r#"{"sale_conditions": {"near": "10000"}}"#);
+        remove_ft(&mut contract, 1000);
+
+        // one sale per call, the sale without the FT is left as is
+        assert!(!process_ft_removal(&mut contract, ms(1000), 1));
+        assert!(!process_ft_removal(&mut contract, ms(1000), 1));
+        assert_eq!(ft_transfers(), vec![(ft().to_string(), accounts(3).to_string(), 1200)]);
+        assert!(process_ft_removal(&mut contract, ms(1000), 1));
+        assert_eq!(ft_transfers(), vec![(ft().to_string(), accounts(2).to_string(), 1500)]);
+
+        let sale = contract.get_sale(contract_and_token_id("1")).unwrap();
+        assert_eq!(sale.sale_conditions.keys().collect::<Vec<_>>(), vec!["near"]);
+        assert!(sale.bids.is_empty());
+        assert!(contract.get_sale(contract_and_token_id("2")).is_none());
+        assert_eq!(contract.get_supply_sales().0, 2);
+        assert_eq!(contract.supported_ft_token_ids(), vec!["near".to_string()]);
+        assert!(contract.get_ft_token_ids_pending_removal().is_empty());
+    }
+
+    #[test]
+    fn bids_need_no_storage_deposit() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", r#"{"sale_conditions": {"near": "1000"}, "is_auction": true}"#);
+        let seller_storage = contract.storage_used_by(accounts(1)).0;
+
+        // accounts(4) never deposited storage, the seller reserved the bid storage when listing
+        offer_near(&mut contract, accounts(4), "1", 2000);
+        offer_near(&mut contract, accounts(2), "1", 3000);
+        assert_eq!(near_transfers(), vec![(accounts(4).to_string(), 2000)]);
+        assert_eq!(contract.storage_used_by(accounts(4)).0, 0);
+        assert_eq!(contract.storage_used_by(accounts(2)).0, 0);
+        assert_eq!(contract.storage_used_by(accounts(1)).0, seller_storage);
+
+        testing_env!(get_context(accounts(1)).attached_deposit(1).build());
+        contract.remove_sale(nft(), "1".to_string());
+        assert_eq!(contract.storage_used_by(accounts(1)).0, 0);
+    }
+
+    #[test]
+    fn listing_reserves_bid_storage_per_ft() {
+        let mut contract = setup_contract();
+        list(&mut contract, "1", r#"{"sale_conditions": {"near": "1000"}, "is_auction": true}"#);
+        let one_ft = contract.storage_used_by(accounts(1)).0;
+        testing_env!(get_context(accounts(1)).attached_deposit(1).build());
+        contract.remove_sale(nft(), "1".to_string());
+
+        list(&mut contract, "1", r#"{"sale_conditions": {"near": "1000", "ft.near": "1000"}, "is_auction": true}"#);
+        let sale_conditions_bytes = 4 + "ft.near".len() as u64 + 16;
+        let reserved_bytes = 4 + "ft.near".len() as u64 + 4 + MAX_BYTES_FOR_BID;
+        assert_eq!(contract.storage_used_by(accounts(1)).0, one_ft + sale_conditions_bytes + reserved_bytes);
+    }
+
+    #[test]
+    #[should_panic(expected = "Insufficient storage paid")]
+    fn listing_needs_deposit_for_bid_history() {
+        testing_env!(get_context(accounts(0)).build());
+        let mut contract = Contract::new(accounts(0), None, Some(20), None);
+        testing_env!(get_context(accounts(1)).attached_deposit(STORAGE_MIN_DEPOSIT).build());
+        contract.storage_deposit(None, None);
+        list(&mut contract, "1", r#"{"sale_conditions": {"near": "1000"}, "is_auction": true}"#);
+    }
+
+    fn resolve_with_payout(contract: &mut Contract, payout: &[(ValidAccountId, Balance)]) -> U128 {
+        let payout: HashMap<String, String> = payout
+            .iter()
+            .map(|(account_id, amount)| (account_id.to_string(), amount.to_string()))
+            .collect();
+        let payout = near_sdk::serde_json::to_vec(&payout).unwrap();
+        resolve_queued_purchase(contract, PromiseResult::Successful(payout))
+    }
+
+    fn buy_with_fee(market_fee_bps: u32) -> Contract {
+        let mut contract = setup_contract();
+        testing_env!(get_context(accounts(0)).build());
+        contract.set_market_fee(market_fee_bps);
+        list(&mut contract, "1", r#"{"sale_conditions": {"near": "10000"}}"#);
+        offer_near(&mut contract, accounts(2), "1", 10000);
+        contract
+    }
+
+    #[test]
+    fn market_fee_comes_off_before_payout() {
+        let mut contract = buy_with_fee(250);
+        assert_eq!(calls("nft_transfer_payout")[0].1["balance"], "9750");
+        assert_eq!(calls("resolve_purchase")[0].1["market_fee"], "250");
+
+        resolve_with_payout(&mut contract, &[(accounts(1), 9000), (accounts(4), 750)]);
+        let mut transfers = near_transfers();
+        transfers.sort();
+        assert_eq!(
+            transfers,
+            vec![(accounts(1).to_string(), 9000), (accounts(4).to_string(), 750)]
+        );
+        assert_eq!(contract.get_fee_balance("near".to_string()).0, 250);
+        assert!(emitted("purchase_completed"));
+    }
+
+    #[test]
+    fn payout_may_leave_one_yocto() {
+        let mut contract = buy_with_fee(250);
+        resolve_with_payout(&mut contract, &[(accounts(1), 9749)]);
+        assert_eq!(near_transfers(), vec![(accounts(1).to_string(), 9749)]);
+        assert_eq!(contract.get_fee_balance("near".to_string()).0, 250);
+    }
+
+    #[test]
+    fn payout_of_full_price_fails_with_fee() {
+        let mut contract = buy_with_fee(250);
+        resolve_with_payout(&mut contract, &[(accounts(1), 10000)]);
+        assert_eq!(near_transfers(), vec![(accounts(2).to_string(), 10000)]);
+        assert_eq!(contract.get_fee_balance("near".to_string()).0, 0);
+        assert!(emitted("payout_failed"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Market fee limited to 1000 bps")]
+    fn market_fee_is_capped() {
+        let mut contract = setup_contract();
+        testing_env!(get_context(accounts(0)).build());
+        contract.set_market_fee(1001);
+    }
+
+    #[test]
+    fn failed_ft_fee_withdrawal_restores_treasury() {
+        let mut contract = setup_contract();
+        testing_env!(get_context(accounts(0)).build());
+        contract.internal_deposit_fees(&ft().into(), 500);
+
+        testing_env!(get_context(accounts(0)).attached_deposit(1).build());
+        contract.withdraw_fees(ft(), U128(300));
+        assert_eq!(contract.get_fee_balance(ft().into()).0, 200);
+        assert_eq!(ft_transfers(), vec![(ft().to_string(), accounts(0).to_string(), 300)]);
+
+        testing_env_with_promise_results(self_context().build(), PromiseResult::Failed);
+        assert!(!contract.resolve_withdraw_fees(ft().into(), U128(300)));
+        assert_eq!(contract.get_fee_balance(ft().into()).0, 500);
+    }
+
+    /// baseline market state, written without a version key, with one typed sale and a bid
+    fn write_v1_snapshot() {
+        testing_env!(get_context(accounts(0)).build());
+        let mut contract = ContractV1 {
+            owner_id: accounts(0).into(),
+            sales: UnorderedMap::new(StorageKey::Sales),
+            by_owner_id: LookupMap::new(StorageKey::ByOwnerId),
+            by_nft_contract_id: LookupMap::new(StorageKey::ByNFTContractId),
+            by_nft_token_type: LookupMap::new(StorageKey::ByNFTTokenType),
+            ft_token_ids: UnorderedSet::new(StorageKey::FTTokenIds),
+            storage_deposits: LookupMap::new(StorageKey::StorageDeposits),
+            bid_history_length: 1,
+        };
+        contract.ft_token_ids.insert(&"near".to_string());
+        contract.storage_deposits.insert(&accounts(1).into(), &(1000 * STORAGE_PRICE_PER_BYTE));
+        let mut bids = HashMap::new();
+        bids.insert("near".to_string(), vec![Bid { owner_id: accounts(2).into(), price: U128(2000) }]);
+        let mut sale_conditions = HashMap::new();
+        sale_conditions.insert("near".to_string(), U128(1000));
+        contract.sales.insert(
+            &contract_and_token_id("A:1"),
+            &SaleV1 {
+                owner_id: accounts(1).into(),
+                approval_id: 1,
+                nft_contract_id: nft().into(),
+                token_id: "A:1".to_string(),
+                sale_conditions,
+                bids,
+                created_at: U64(0),
+                is_auction: true,
+                token_type: Some("A".to_string()),
+            },
+        );
+
+        let mut by_owner_id = UnorderedSet::new(StorageKey::ByOwnerIdInner {
+            account_id_hash: hash_account_id(&accounts(1).into()),
+        });
+        by_owner_id.insert(&contract_and_token_id("A:1"));
+        contract.by_owner_id.insert(&accounts(1).into(), &by_owner_id);
+        let mut by_nft_contract_id = UnorderedSet::new(StorageKey::ByNFTContractIdInner {
+            account_id_hash: hash_account_id(&nft().into()),
+        });
+        by_nft_contract_id.insert(&"A:1".to_string());
+        contract.by_nft_contract_id.insert(&nft().into(), &by_nft_contract_id);
+        let mut by_nft_token_type = UnorderedSet::new(StorageKey::ByNFTTokenTypeInner {
+            token_type_hash: hash_account_id(&"A".to_string()),
+        });
+        by_nft_token_type.insert(&contract_and_token_id("A:1"));
+        contract.by_nft_token_type.insert(&"A".to_string(), &by_nft_token_type);
+
+        env::state_write(&contract);
+    }
+
+    #[test]
+    fn migrate_from_v1_snapshot() {
+        write_v1_snapshot();
+
+        testing_env!(get_context(accounts(0)).build());
+        let mut contract = Contract::migrate();
+
+        assert_eq!(contract.contract_version(), CONTRACT_VERSION);
+        assert_eq!(contract.get_market_fee(), 0);
+        // sales are converted by migrate_sales, views already show them
+        assert_eq!(contract.get_supply_sales_to_migrate().0, 1);
+        assert_eq!(contract.get_supply_sales().0, 1);
+        assert!(contract.get_sale(contract_and_token_id("A:1")).is_some());
+        assert!(!contract.migrate_sales(0));
+        assert!(contract.migrate_sales(10));
+        assert_eq!(contract.get_supply_sales_to_migrate().0, 0);
+
+        let sale = contract.get_sale(contract_and_token_id("A:1")).unwrap();
+        assert!(sale.is_auction && sale.ends_at.is_none() && sale.dutch_auction.is_none());
+        assert_eq!(sale.bids["near"][0].owner_id, accounts(2).to_string());
+        assert_eq!(contract.get_sales_by_owner_id(accounts(1).into(), U64(0), 10).len(), 1);
+        assert_eq!(contract.get_sales_by_nft_contract_id(nft().into(), U64(0), 10).len(), 1);
+        assert_eq!(contract.get_sales_by_nft_token_type("A".to_string(), U64(0), 10).len(), 1);
+        assert!(contract.storage_used_by(accounts(1)).0 > 0);
+
+        // migrated sales complete like new ones
+        testing_env!(get_context(accounts(1)).build());
+        contract.accept_offer(nft(), "A:1".to_string(), ValidAccountId::try_from("near").unwrap());
+        assert_eq!(calls("nft_transfer_payout")[0].1["receiver_id"], accounts(2).to_string());
+        assert_eq!(contract.get_supply_sales().0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Sale not migrated yet")]
+    fn unmigrated_sale_cannot_be_listed_again() {
+        write_v1_snapshot();
+
+        testing_env!(get_context(accounts(0)).build());
+        let mut contract = Contract::migrate();
+        list(&mut contract, "A:1", r#"{"sale_conditions": {"near": "1000"}, "token_type": "A"}"#);
+    }
+
+    #[test]
+    #[should_panic(expected = "Owner's method")]
+    fn migrate_requires_owner() {
+        write_v1_snapshot();
+
+        testing_env!(get_context(accounts(1)).build());
+        Contract::migrate();
     }
 }
@@ -0,0 +1,177 @@
+use crate::*;
+
+/// market state version, 1 is the layout before fees, offers and auctions (see ContractV1 and SaleV1).
+/// `Sale` is part of the state too, changing it needs a new version and a conversion like migrate_sales
+pub const CONTRACT_VERSION: u32 = 2;
+/// State written before versioning has no version key and is version 1
+const STATE_VERSION_KEY: &[u8] = b"VERSION";
+
+/// Sale layout of version 1, before timed and dutch auctions
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct SaleV1 {
+    pub owner_id: AccountId,
+    pub approval_id: u64,
+    pub nft_contract_id: String,
+    pub token_id: String,
+    pub sale_conditions: SaleConditions,
+    pub bids: Bids,
+    pub created_at: U64,
+    pub is_auction: bool,
+    pub token_type: Option<String>,
+}
+
+/// Contract layout of version 1, before fees, offers, FT removal and per byte storage
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractV1 {
+    pub owner_id: AccountId,
+    pub sales: UnorderedMap<ContractAndTokenId, SaleV1>,
+    pub by_owner_id: LookupMap<AccountId, UnorderedSet<ContractAndTokenId>>,
+    pub by_nft_contract_id: LookupMap<AccountId, UnorderedSet<TokenId>>,
+    pub by_nft_token_type: LookupMap<AccountId, UnorderedSet<ContractAndTokenId>>,
+    pub ft_token_ids: UnorderedSet<AccountId>,
+    pub storage_deposits: LookupMap<AccountId, Balance>,
+    pub bid_history_length: u8,
+}
+
+pub enum VersionedContract {
+    V1(Box<ContractV1>),
+    Current(Box<Contract>),
+}
+
+impl VersionedContract {
+    /// reads the contract state using the layout of the stored version
+    pub(crate) fn read() -> Self {
+        match stored_contract_version() {
+            1 => VersionedContract::V1(Box::new(env::state_read().expect("No contract state"))),
+            CONTRACT_VERSION => VersionedContract::Current(Box::new(env::state_read().expect("No contract state"))),
+            version => env::panic(format!("Unknown contract version {}", version).as_bytes()),
+        }
+    }
+
+    pub(crate) fn owner_id(&self) -> &AccountId {
+        match self {
+            VersionedContract::V1(contract) => &contract.owner_id,
+            VersionedContract::Current(contract) => &contract.owner_id,
+        }
+    }
+
+    /// upgrades one layout at a time until the current one
+    pub(crate) fn into_current(self) -> Contract {
+        match self {
+            VersionedContract::V1(contract) => {
+                let contract = *contract;
+                Contract {
+                    owner_id: contract.owner_id,
+                    // version 1 sales keep their prefix until migrate_sales converts them
+                    sales: UnorderedMap::new(StorageKey::MigratedSales),
+                    legacy_sales: contract.sales,
+                    by_owner_id: contract.by_owner_id,
+                    by_nft_contract_id: contract.by_nft_contract_id,
+                    by_nft_token_type: contract.by_nft_token_type,
+                    ft_token_ids: contract.ft_token_ids,
+                    storage_deposits: contract.storage_deposits,
+                    storage_used: LookupMap::new(StorageKey::StorageUsed),
+                    bid_history_length: contract.bid_history_length,
+                    market_fee_bps: 0,
+                    treasury: LookupMap::new(StorageKey::Treasury),
+                    collection_offers: UnorderedMap::new(StorageKey::CollectionOffers),
+                    collection_offers_by_type: LookupMap::new(StorageKey::CollectionOffersByType),
+                    collection_offers_by_owner_id: LookupMap::new(StorageKey::CollectionOffersByOwnerId),
+                    next_collection_offer_id: 0,
+                    token_offers: LookupMap::new(StorageKey::TokenOffers),
+                    token_offers_by_owner_id: LookupMap::new(StorageKey::TokenOffersByOwnerId),
+                    ft_token_removals: UnorderedMap::new(StorageKey::FtTokenRemovals),
+                }
+            }
+            VersionedContract::Current(contract) => *contract,
+        }
+    }
+}
+
+impl From<SaleV1> for Sale {
+    fn from(sale: SaleV1) -> Self {
+        Sale {
+            owner_id: sale.owner_id,
+            approval_id: sale.approval_id,
+            nft_contract_id: sale.nft_contract_id,
+            token_id: sale.token_id,
+            sale_conditions: sale.sale_conditions,
+            bids: sale.bids,
+            created_at: sale.created_at,
+            is_auction: sale.is_auction,
+            token_type: sale.token_type,
+            ends_at: None,
+            min_bid_increment: None,
+            extension_window: None,
+            bid_lock_window: None,
+            dutch_auction: None,
+        }
+    }
+}
+
+impl Contract {
+    /// views see sales that migrate_sales hasn't converted yet as they will be
+    pub(crate) fn internal_get_sale(&self, contract_and_token_id: &ContractAndTokenId) -> Option<Sale> {
+        self.sales
+            .get(contract_and_token_id)
+            .or_else(|| self.legacy_sales.get(contract_and_token_id).map(Sale::from))
+    }
+}
+
+pub(crate) fn stored_contract_version() -> u32 {
+    env::storage_read(STATE_VERSION_KEY)
+        .map(|version| u32::try_from_slice(&version).expect("Invalid contract version"))
+        .unwrap_or(1)
+}
+
+pub(crate) fn write_contract_version() {
+    env::storage_write(STATE_VERSION_KEY, &CONTRACT_VERSION.try_to_vec().unwrap());
+}
+
+#[near_bindgen]
+impl Contract {
+    /// after deploying, the owner (or the contract account) calls migrate to read the stored layout and
+    /// write the current one. Sales of version 1 are only moved aside, convert them with migrate_sales.
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let state = VersionedContract::read();
+        let predecessor_account_id = env::predecessor_account_id();
+        assert!(
+            &predecessor_account_id == state.owner_id() || predecessor_account_id == env::current_account_id(),
+            "Owner's method"
+        );
+        let this = state.into_current();
+        write_contract_version();
+        this
+    }
+
+    /// anyone, converts up to limit version 1 sales, true once none are left. Until then they show in
+    /// the views but can't be bought, bid on or listed again. Sales were paid with a flat deposit, each
+    /// seller is now charged the bytes their sale and its reserved bids use (without checking the deposit,
+    /// listings stay up)
+    pub fn migrate_sales(&mut self, limit: u64) -> bool {
+        let stop = self.legacy_sales.len().saturating_sub(limit);
+        while self.legacy_sales.len() > stop {
+            let contract_and_token_id = self.legacy_sales.keys_as_vector().get(self.legacy_sales.len() - 1).unwrap();
+            let sale = Sale::from(self.legacy_sales.remove(&contract_and_token_id).unwrap());
+            // indexes are written again so the seller is charged for them too
+            self.internal_remove_sale_indexes(&contract_and_token_id, &sale);
+            let initial_storage_usage = env::storage_usage();
+            self.internal_insert_sale(&sale);
+            let bytes = env::storage_usage() - initial_storage_usage - bytes_for_bids(&sale.bids)
+                + bytes_reserved_for_bids(&sale, self.bid_history_length);
+            let used = self.storage_used.get(&sale.owner_id).unwrap_or(0) + bytes;
+            self.storage_used.insert(&sale.owner_id, &used);
+        }
+        self.legacy_sales.is_empty()
+    }
+
+    pub fn contract_version(&self) -> u32 {
+        stored_contract_version()
+    }
+
+    /// version 1 sales migrate_sales hasn't converted yet
+    pub fn get_supply_sales_to_migrate(&self) -> U64 {
+        U64(self.legacy_sales.len())
+    }
+}
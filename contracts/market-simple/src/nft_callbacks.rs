@@ -9,9 +9,33 @@ pub struct SaleArgs {
     pub token_type: TokenType,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_auction: Option<bool>,
+    /// CUSTOM - timed auction end in ms, settled by anyone with settle_auction
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ends_at: Option<U64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_bid_increment: Option<BidIncrement>,
+    /// bids within this many ms of ends_at push ends_at out to now + extension_window
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extension_window: Option<U64>,
+    /// bidders can't cancel within this many ms of ends_at
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bid_lock_window: Option<U64>,
+    /// CUSTOM - descending price per FT, leave sale_conditions empty (they are set to the floor prices)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dutch_auction: Option<DutchAuctions>,
 }
 
-trait NonFungibleTokenApprovalsReceiver {
+/// nft_approve msg, either a fill of a collection offer, an accepted token offer or a new sale
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(untagged)]
+enum ApproveArgs {
+    FillOffer(FillOfferArgs),
+    AcceptOffer(AcceptOfferArgs),
+    Sale(SaleArgs),
+}
+
+pub trait NonFungibleTokenApprovalsReceiver {
     fn nft_on_approve(
         &mut self,
         token_id: TokenId,
@@ -19,6 +43,13 @@ trait NonFungibleTokenApprovalsReceiver {
         approval_id: u64,
         msg: String,
     );
+
+    fn nft_on_revoke(
+        &mut self,
+        token_id: TokenId,
+        owner_id: ValidAccountId,
+        approval_id: u64,
+    );
 }
 
 #[near_bindgen]
@@ -47,102 +78,128 @@ impl NonFungibleTokenApprovalsReceiver for Contract {
             "owner_id should be signer_id"
         );
 
-        // enforce signer's storage is enough to cover + 1 more sale 
-
-        let storage_amount = self.storage_amount().0;
-        let owner_paid_storage = self.storage_deposits.get(&signer_id).unwrap_or(0);
-        let signer_storage_required = (self.get_supply_by_owner_id(signer_id).0 + 1) as u128 * storage_amount;
-        assert!(
-            owner_paid_storage >= signer_storage_required,
-            "Insufficient storage paid: {}, for {} sales at {} rate of per sale",
-            owner_paid_storage, signer_storage_required / STORAGE_PER_SALE, STORAGE_PER_SALE
-        );
+        let args = near_sdk::serde_json::from_str(&msg).expect("Not valid SaleArgs");
+        let sale_args = match args {
+            // CUSTOM - sell into a collection offer instead of listing
+            ApproveArgs::FillOffer(fill_offer_args) => {
+                self.internal_fill_collection_offer(
+                    nft_contract_id,
+                    token_id,
+                    owner_id.into(),
+                    approval_id,
+                    fill_offer_args,
+                );
+                return;
+            }
+            // CUSTOM - sell to an offer on this token
+            ApproveArgs::AcceptOffer(accept_offer_args) => {
+                self.internal_accept_token_offer(
+                    nft_contract_id,
+                    token_id,
+                    owner_id.into(),
+                    approval_id,
+                    accept_offer_args,
+                );
+                return;
+            }
+            ApproveArgs::Sale(sale_args) => sale_args,
+        };
+
+        let SaleArgs {
+            sale_conditions,
+            token_type,
+            is_auction,
+            ends_at,
+            min_bid_increment,
+            extension_window,
+            bid_lock_window,
+            dutch_auction,
+        } = sale_args;
+
+        if let Some(ends_at) = ends_at {
+            assert!(is_auction.unwrap_or(false), "ends_at is only for auctions");
+            assert!(ends_at.0 > now_ms(), "ends_at must be in the future");
+            assert_eq!(sale_conditions.len(), 1, "Timed auctions take bids in a single token");
+        } else {
+            assert!(extension_window.is_none(), "extension_window requires ends_at");
+            assert!(bid_lock_window.is_none(), "bid_lock_window requires ends_at");
+        }
 
-        let SaleArgs { sale_conditions, token_type, is_auction } =
-            near_sdk::serde_json::from_str(&msg).expect("Not valid SaleArgs");
+        let sale_conditions = if let Some(dutch_auction) = &dutch_auction {
+            assert!(sale_conditions.is_empty(), "Dutch auction prices are set in dutch_auction");
+            assert!(!is_auction.unwrap_or(false), "Dutch auctions don't take bids");
+            assert!(!dutch_auction.is_empty(), "Dutch auction needs at least one token");
+            dutch_auction
+                .iter()
+                .map(|(ft_token_id, conditions)| {
+                    conditions.assert_valid();
+                    (ft_token_id.clone(), conditions.floor_price)
+                })
+                .collect()
+        } else {
+            sale_conditions
+        };
 
         
         for (ft_token_id, _price) in sale_conditions.clone() {
-            if !self.ft_token_ids.contains(&ft_token_id) {
-                env::panic(
-                    format!("Token {} not supported by this market", ft_token_id).as_bytes(),
-                );
-            }
+            self.assert_ft_token_accepted(&ft_token_id);
         }
 
         // env::log(format!("add_sale for owner: {}", &owner_id).as_bytes());
 
         let bids = HashMap::new();
 
-        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMETER, token_id);
-        self.sales.insert(
-            &contract_and_token_id,
-            &Sale {
-                owner_id: owner_id.clone().into(),
-                approval_id,
-                nft_contract_id: nft_contract_id.clone(),
-                token_id: token_id.clone(),
-                sale_conditions,
-                bids,
-                created_at: U64(env::block_timestamp()/1000000),
-                token_type: token_type.clone(),
-                is_auction: is_auction.unwrap_or(false),
-            },
-        );
-
-        // extra for views
-
-        let mut by_owner_id = self.by_owner_id.get(owner_id.as_ref()).unwrap_or_else(|| {
-            UnorderedSet::new(
-                StorageKey::ByOwnerIdInner {
-                    account_id_hash: hash_account_id(owner_id.as_ref()),
-                }
-                .try_to_vec()
-                .unwrap(),
-            )
+        MarketEventKind::SaleCreated(vec![SaleCreatedLog {
+            owner_id: owner_id.clone().into(),
+            nft_contract_id: nft_contract_id.clone(),
+            token_id: token_id.clone(),
+            approval_id,
+            sale_conditions: sale_conditions.clone(),
+            is_auction: is_auction.unwrap_or(false),
+            token_type: token_type.clone(),
+            ends_at,
+        }])
+        .emit();
+
+        // panics if the signer's storage deposit doesn't cover the sale
+        self.internal_add_sale(Sale {
+            owner_id: owner_id.into(),
+            approval_id,
+            nft_contract_id,
+            token_id,
+            sale_conditions,
+            bids,
+            created_at: U64(env::block_timestamp()/1000000),
+            token_type,
+            is_auction: is_auction.unwrap_or(false),
+            ends_at,
+            min_bid_increment,
+            extension_window,
+            bid_lock_window,
+            dutch_auction,
         });
+    }
 
-        let owner_occupied_storage = u128::from(by_owner_id.len()) * STORAGE_PER_SALE;
-        assert!(
-            owner_paid_storage > owner_occupied_storage,
-            "User has more sales than storage paid"
-        );
-        by_owner_id.insert(&contract_and_token_id);
-        self.by_owner_id.insert(owner_id.as_ref(), &by_owner_id);
-
-        let mut by_nft_contract_id = self
-            .by_nft_contract_id
-            .get(&nft_contract_id)
-            .unwrap_or_else(|| {
-                UnorderedSet::new(
-                    StorageKey::ByNFTContractIdInner {
-                        account_id_hash: hash_account_id(&nft_contract_id),
-                    }
-                    .try_to_vec()
-                    .unwrap(),
-                )
-            });
-        by_nft_contract_id.insert(&token_id);
-        self.by_nft_contract_id
-            .insert(&nft_contract_id, &by_nft_contract_id);
-
-        if let Some(token_type) = token_type {
-            assert!(token_id.contains(&token_type), "TokenType should be substr of TokenId");
-            let mut by_nft_token_type = self
-                .by_nft_token_type
-                .get(&token_type)
-                .unwrap_or_else(|| {
-                    UnorderedSet::new(
-                        StorageKey::ByNFTTokenTypeInner {
-                            token_type_hash: hash_account_id(&token_type),
-                        }
-                        .try_to_vec()
-                        .unwrap(),
-                    )
-                });
-                by_nft_token_type.insert(&contract_and_token_id);
-            self.by_nft_token_type
-                .insert(&token_type, &by_nft_token_type);
+    /// where we remove the sale because the market can no longer transfer the token
+
+    fn nft_on_revoke(
+        &mut self,
+        token_id: TokenId,
+        owner_id: ValidAccountId,
+        approval_id: u64,
+    ) {
+        let nft_contract_id = env::predecessor_account_id();
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMETER, token_id);
+        let sale = if let Some(sale) = self.sales.get(&contract_and_token_id) {
+            sale
+        } else {
+            return;
+        };
+        // ignore revokes of an older approval, the token may have been listed again since
+        if &sale.owner_id != owner_id.as_ref() || sale.approval_id != approval_id {
+            return;
         }
+        let sale = self.internal_remove_sale(nft_contract_id, token_id);
+        self.refund_all_bids(&sale);
     }
 }
@@ -1,14 +1,13 @@
 use crate::*;
-use near_sdk::promise_result_as_success;
 
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct Bid {
     pub owner_id: AccountId,
     pub price: U128,
 }
 
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct Sale {
     pub owner_id: AccountId,
@@ -20,6 +19,97 @@ pub struct Sale {
     pub created_at: U64,
     pub is_auction: bool,
     pub token_type: Option<String>,
+
+    // CUSTOM - timed auctions, timestamps in ms like created_at
+    pub ends_at: Option<U64>,
+    pub min_bid_increment: Option<BidIncrement>,
+    pub extension_window: Option<U64>,
+    /// bids can't be cancelled within this many ms of ends_at
+    pub bid_lock_window: Option<U64>,
+
+    // CUSTOM - dutch auctions, sale_conditions hold the floor prices
+    pub dutch_auction: Option<DutchAuctions>,
+}
+
+/// minimum raise over the current bid, absolute amounts are in the bid's token
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum BidIncrement {
+    Absolute(U128),
+    BasisPoints(u32),
+}
+
+/// descending price for one FT, timestamps in ms like created_at
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DutchAuction {
+    pub start_price: U128,
+    pub floor_price: U128,
+    pub starts_at: U64,
+    pub decay: PriceDecay,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum PriceDecay {
+    /// falls evenly from start_price to floor_price over duration ms
+    Linear { duration: U64 },
+    /// falls by step_amount every step_interval ms until floor_price
+    Stepwise { step_interval: U64, step_amount: U128 },
+}
+
+impl DutchAuction {
+    pub(crate) fn assert_valid(&self) {
+        assert!(self.start_price.0 >= self.floor_price.0, "start_price must be at least floor_price");
+        match &self.decay {
+            PriceDecay::Linear { duration } => assert!(duration.0 > 0, "duration must be greater than 0"),
+            PriceDecay::Stepwise { step_interval, .. } => assert!(step_interval.0 > 0, "step_interval must be greater than 0"),
+        }
+    }
+
+    pub(crate) fn price_at(&self, timestamp: u64) -> Balance {
+        if timestamp <= self.starts_at.0 {
+            return self.start_price.0;
+        }
+        let elapsed = timestamp - self.starts_at.0;
+        let range = self.start_price.0 - self.floor_price.0;
+        let decrease = match &self.decay {
+            PriceDecay::Linear { duration } => {
+                if elapsed >= duration.0 {
+                    range
+                } else {
+                    range * u128::from(elapsed) / u128::from(duration.0)
+                }
+            }
+            PriceDecay::Stepwise { step_interval, step_amount } => {
+                u128::from(elapsed / step_interval.0).saturating_mul(step_amount.0)
+            }
+        };
+        self.start_price.0 - min(decrease, range)
+    }
+}
+
+impl Sale {
+    /// current price when the sale is a dutch auction, None for regular sales and auctions
+    pub(crate) fn dutch_price(&self, ft_token_id: &FungibleTokenId) -> Option<Balance> {
+        self.dutch_auction.as_ref().map(|dutch_auction| {
+            dutch_auction
+                .get(ft_token_id)
+                .expect("Not for sale in that token type")
+                .price_at(now_ms())
+        })
+    }
+}
+
+impl BidIncrement {
+    pub(crate) fn min_next_bid(&self, current_bid: Balance) -> Balance {
+        match self {
+            BidIncrement::Absolute(amount) => current_bid + amount.0,
+            BidIncrement::BasisPoints(bps) => current_bid + current_bid * u128::from(*bps) / 10_000u128,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -29,6 +119,29 @@ pub struct PurchaseArgs {
     pub token_id: TokenId,
 }
 
+/// buyer side of a sale being bought, passed to resolve_purchase
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Purchase {
+    pub ft_token_id: FungibleTokenId,
+    pub buyer_id: AccountId,
+    pub price: U128,
+    /// the price is held by the market (accepted bids) instead of being attached to the purchase
+    pub escrowed: bool,
+}
+
+/// escrowed offer (collection or token offer) being filled, the buyer's funds are already in the market
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OfferPurchase {
+    pub buyer_id: AccountId,
+    pub seller_id: AccountId,
+    pub nft_contract_id: AccountId,
+    pub token_id: TokenId,
+    pub ft_token_id: FungibleTokenId,
+    pub price: U128,
+}
+
 #[near_bindgen]
 impl Contract {
     /// for add sale see: nft_callbacks.rs
@@ -40,7 +153,50 @@ impl Contract {
         let sale = self.internal_remove_sale(nft_contract_id.into(), token_id);
         let owner_id = env::predecessor_account_id();
         assert_eq!(owner_id, sale.owner_id, "Must be sale owner");
-        self.refund_all_bids(&sale.bids);
+        self.refund_all_bids(&sale);
+    }
+
+    /// only owner, removes stale listings e.g. tokens sold on another market (see README known issues)
+    /// returns whether each sale was removed, missing sales are skipped
+    #[payable]
+    pub fn remove_sales_batch(&mut self, contract_and_token_ids: Vec<ContractAndTokenId>) -> Vec<bool> {
+        assert_one_yocto();
+        self.assert_owner();
+        let mut removed = vec![];
+        for contract_and_token_id in contract_and_token_ids {
+            if self.sales.get(&contract_and_token_id).is_none() {
+                removed.push(false);
+                continue;
+            }
+            let mut split = contract_and_token_id.splitn(2, DELIMETER);
+            let nft_contract_id = split.next().unwrap().to_string();
+            let token_id = split.next().expect("Invalid ContractAndTokenId").to_string();
+            let sale = self.internal_remove_sale(nft_contract_id, token_id);
+            self.refund_all_bids(&sale);
+            removed.push(true);
+        }
+        removed
+    }
+
+    /// anyone can prune a sale that can no longer complete,
+    /// checks nft_token on the NFT contract and removes the sale if the owner or approval changed
+    pub fn prune_sale(&mut self, nft_contract_id: ValidAccountId, token_id: String) -> Promise {
+        let contract_id: AccountId = nft_contract_id.into();
+        let contract_and_token_id = format!("{}{}{}", contract_id, DELIMETER, token_id);
+        assert!(self.sales.get(&contract_and_token_id).is_some(), "No sale");
+        ext_contract::nft_token(
+            token_id.clone(),
+            &contract_id,
+            NO_DEPOSIT,
+            GAS_FOR_NFT_TOKEN,
+        )
+        .then(ext_self::resolve_prune_sale(
+            contract_id,
+            token_id,
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_RESOLVE_PRUNE,
+        ))
     }
 
     #[payable]
@@ -60,18 +216,35 @@ impl Contract {
             sale.owner_id,
             "Must be sale owner"
         );
-        if !self.ft_token_ids.contains(ft_token_id.as_ref()) {
-            env::panic(format!("Token {} not supported by this market", ft_token_id).as_bytes());
+        self.assert_ft_token_accepted(ft_token_id.as_ref());
+        assert!(sale.dutch_auction.is_none(), "Dutch auction prices follow their schedule");
+        if sale.ends_at.is_some() {
+            assert!(
+                sale.sale_conditions.contains_key(ft_token_id.as_ref()),
+                "Timed auctions take bids in a single token"
+            );
         }
-        sale.sale_conditions.insert(ft_token_id.into(), price);
-        self.sales.insert(&contract_and_token_id, &sale);
+        sale.sale_conditions.insert(ft_token_id.clone().into(), price);
+        self.internal_update_sale(&contract_and_token_id, &sale);
+
+        MarketEventKind::PriceUpdated(vec![PriceUpdatedLog {
+            owner_id: sale.owner_id,
+            nft_contract_id: contract_id,
+            token_id,
+            ft_token_id: ft_token_id.into(),
+            price,
+        }])
+        .emit();
     }
 
     #[payable]
     pub fn offer(&mut self, nft_contract_id: ValidAccountId, token_id: String) {
         let contract_id: AccountId = nft_contract_id.into();
         let contract_and_token_id = format!("{}{}{}", contract_id, DELIMETER, token_id);
-        let mut sale = self.sales.get(&contract_and_token_id).expect("No sale");
+        let mut sale = self
+            .sales
+            .get(&contract_and_token_id)
+            .expect("No sale, use make_token_offer for unlisted tokens");
         let buyer_id = env::predecessor_account_id();
         assert_ne!(sale.owner_id, buyer_id, "Cannot bid on your own sale.");
         let ft_token_id = "near".to_string();
@@ -84,6 +257,27 @@ impl Contract {
         let deposit = env::attached_deposit();
         assert!(deposit > 0, "Attached deposit must be greater than 0");
 
+        // CUSTOM - dutch auctions sell at the current price and refund the rest
+        if let Some(current_price) = sale.dutch_price(&ft_token_id) {
+            assert!(
+                deposit >= current_price,
+                "Attached deposit must be at least the current price: {}",
+                current_price
+            );
+            if deposit > current_price {
+                Promise::new(buyer_id.clone()).transfer(deposit - current_price);
+            }
+            self.process_purchase(
+                contract_id,
+                token_id,
+                ft_token_id,
+                U128(current_price),
+                buyer_id,
+                false,
+            );
+            return;
+        }
+
         if !sale.is_auction && deposit == price {
             self.process_purchase(
                 contract_id,
@@ -91,6 +285,7 @@ impl Contract {
                 ft_token_id,
                 U128(deposit),
                 buyer_id,
+                false,
             );
         } else {
             if sale.is_auction && price > 0 {
@@ -106,6 +301,42 @@ impl Contract {
         }
     }
 
+    /// bidder pulls out their current bid and gets refunded,
+    /// timed auctions lock bids once they end (and within bid_lock_window of the end)
+    #[payable]
+    pub fn cancel_bid(
+        &mut self,
+        nft_contract_id: ValidAccountId,
+        token_id: String,
+        ft_token_id: ValidAccountId,
+    ) {
+        assert_one_yocto();
+        let contract_id: AccountId = nft_contract_id.into();
+        let contract_and_token_id = format!("{}{}{}", contract_id, DELIMETER, token_id);
+        let mut sale = self.sales.get(&contract_and_token_id).expect("No sale");
+
+        if let Some(ends_at) = sale.ends_at {
+            let lock_window = sale.bid_lock_window.map(|window| window.0).unwrap_or(0);
+            assert!(
+                now_ms() < ends_at.0.saturating_sub(lock_window),
+                "Bids can't be cancelled at the end of the auction"
+            );
+        }
+
+        // only the last bid is live, earlier bids in the history were refunded when outbid
+        let bids_for_token_id = sale.bids.get(ft_token_id.as_ref()).expect("No bids");
+        let bid = bids_for_token_id[bids_for_token_id.len()-1].clone();
+        assert_eq!(
+            env::predecessor_account_id(),
+            bid.owner_id,
+            "Must be current bid owner"
+        );
+        sale.bids.remove(ft_token_id.as_ref());
+        self.internal_update_sale(&contract_and_token_id, &sale);
+
+        self.internal_refund_bid(&contract_id, &token_id, ft_token_id.as_ref(), &bid);
+    }
+
     #[private]
     pub fn add_bid(
         &mut self,
@@ -115,9 +346,23 @@ impl Contract {
         buyer_id: AccountId,
         sale: &mut Sale,
     ) {
+        // CUSTOM - no new bids in an FT that is being removed
+        self.assert_ft_token_accepted(&ft_token_id);
+
+        // CUSTOM - timed auctions reject late bids and extend the deadline for bids close to it
+        if let Some(ends_at) = sale.ends_at {
+            let now = now_ms();
+            assert!(now < ends_at.0, "Auction has ended");
+            if let Some(extension_window) = sale.extension_window {
+                if ends_at.0 - now < extension_window.0 {
+                    sale.ends_at = Some(U64(now + extension_window.0));
+                }
+            }
+        }
+
         // store a bid and refund any current bid lower
         let new_bid = Bid {
-            owner_id: buyer_id,
+            owner_id: buyer_id.clone(),
             price: U128(amount),
         };
         
@@ -130,26 +375,32 @@ impl Contract {
                 "Can't pay less than or equal to current bid price: {}",
                 current_bid.price.0
             );
-            if ft_token_id == "near" {
-                Promise::new(current_bid.owner_id.clone()).transfer(u128::from(current_bid.price));
-            } else {
-                ext_contract::ft_transfer(
-                    current_bid.owner_id.clone(),
-                    current_bid.price,
-                    None,
-                    &ft_token_id,
-                    1,
-                    GAS_FOR_FT_TRANSFER,
+            if let Some(min_bid_increment) = &sale.min_bid_increment {
+                let min_next_bid = min_bid_increment.min_next_bid(current_bid.price.0);
+                assert!(
+                    amount >= min_next_bid,
+                    "Bid must be at least {}",
+                    min_next_bid
                 );
             }
+            self.internal_refund_bid(&sale.nft_contract_id, &sale.token_id, &ft_token_id, current_bid);
         }
         
         bids_for_token_id.push(new_bid);
+
+        MarketEventKind::BidPlaced(vec![BidLog {
+            bidder_id: buyer_id,
+            nft_contract_id: sale.nft_contract_id.clone(),
+            token_id: sale.token_id.clone(),
+            ft_token_id,
+            price: U128(amount),
+        }])
+        .emit();
         if bids_for_token_id.len() > self.bid_history_length as usize {
             bids_for_token_id.remove(0);
         }
         
-        self.sales.insert(&contract_and_token_id, &sale);
+        self.internal_update_sale(&contract_and_token_id, sale);
     }
 
     pub fn accept_offer(
@@ -159,22 +410,63 @@ impl Contract {
         ft_token_id: ValidAccountId,
     ) {
         let contract_id: AccountId = nft_contract_id.into();
-        let contract_and_token_id = format!("{}{}{}", contract_id.clone(), DELIMETER, token_id.clone());
+        let contract_and_token_id = format!("{}{}{}", contract_id, DELIMETER, token_id);
         // remove bid before proceeding to process purchase
         let mut sale = self.sales.get(&contract_and_token_id).expect("No sale");
+        assert_eq!(
+            env::predecessor_account_id(),
+            sale.owner_id,
+            "Must be sale owner"
+        );
+        assert!(sale.ends_at.is_none(), "Timed auctions are settled with settle_auction");
         let bids_for_token_id = sale.bids.remove(ft_token_id.as_ref()).expect("No bids");
         let bid = &bids_for_token_id[bids_for_token_id.len()-1];
-        self.sales.insert(&contract_and_token_id, &sale);
-        // panics at `self.internal_remove_sale` and reverts above if predecessor is not sale.owner_id
+        self.internal_update_sale(&contract_and_token_id, &sale);
         self.process_purchase(
             contract_id,
             token_id,
             ft_token_id.into(),
             bid.price,
             bid.owner_id.clone(),
+            true,
         );
     }
 
+    /// anyone can settle a timed auction once ends_at has passed, the highest bid wins
+    pub fn settle_auction(
+        &mut self,
+        nft_contract_id: ValidAccountId,
+        token_id: String,
+    ) {
+        let contract_id: AccountId = nft_contract_id.into();
+        let contract_and_token_id = format!("{}{}{}", contract_id, DELIMETER, token_id);
+        let mut sale = self.sales.get(&contract_and_token_id).expect("No sale");
+        let ends_at = sale.ends_at.expect("Not a timed auction");
+        assert!(now_ms() >= ends_at.0, "Auction has not ended");
+
+        // timed auctions take bids in a single token
+        let ft_token_id = sale.sale_conditions.keys().next().expect("No sale conditions").clone();
+        let bids_for_token_id = if let Some(bids_for_token_id) = sale.bids.remove(&ft_token_id) {
+            bids_for_token_id
+        } else {
+            // no bids, nothing to sell
+            let sale = self.internal_remove_sale(contract_id, token_id);
+            self.refund_all_bids(&sale);
+            return;
+        };
+        let bid = &bids_for_token_id[bids_for_token_id.len()-1];
+        self.internal_update_sale(&contract_and_token_id, &sale);
+        self.process_purchase(
+            contract_id,
+            token_id,
+            ft_token_id,
+            bid.price,
+            bid.owner_id.clone(),
+            true,
+        );
+    }
+
+    /// escrowed: the price is held by the market (accepted bids) instead of being attached to this purchase
     #[private]
     pub fn process_purchase(
         &mut self,
@@ -183,25 +475,33 @@ impl Contract {
         ft_token_id: AccountId,
         price: U128,
         buyer_id: AccountId,
+        escrowed: bool,
     ) -> Promise {
         let sale = self.internal_remove_sale(nft_contract_id.clone(), token_id.clone());
 
+        // CUSTOM - market fee comes off the top, the NFT contract splits the rest
+        let market_fee = U128(market_fee_for_price(self.market_fee_bps, price.0));
+
         ext_contract::nft_transfer_payout(
             buyer_id.clone(),
             token_id,
             sale.approval_id,
             "payout from market".to_string(),
-            price,
+            U128(price.0 - market_fee.0),
 			10,
             &nft_contract_id,
             1,
             GAS_FOR_NFT_TRANSFER,
         )
         .then(ext_self::resolve_purchase(
-            ft_token_id,
-            buyer_id,
             sale,
-            price,
+            Purchase {
+                ft_token_id,
+                buyer_id,
+                price,
+                escrowed,
+            },
+            market_fee,
             &env::current_account_id(),
             NO_DEPOSIT,
             GAS_FOR_ROYALTIES,
@@ -210,86 +510,225 @@ impl Contract {
 
     /// self callback
 
+    #[private]
+    pub fn resolve_prune_sale(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: String,
+    ) -> bool {
+        // keep the sale if the NFT contract call failed, we can't tell if it's stale
+        let token = if let Some(token) = promise_result_as_token() {
+            token
+        } else {
+            return false;
+        };
+
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMETER, token_id);
+        let sale = if let Some(sale) = self.sales.get(&contract_and_token_id) {
+            sale
+        } else {
+            return false;
+        };
+        if is_sale_valid_for_token(&sale, &token) {
+            return false;
+        }
+
+        let sale = self.internal_remove_sale(nft_contract_id, token_id);
+        self.refund_all_bids(&sale);
+        true
+    }
+
+    #[private]
+    pub fn resolve_relist_sale(&mut self, sale: Sale) -> bool {
+        let token = if let Some(token) = promise_result_as_token() {
+            token
+        } else {
+            return false;
+        };
+        if !is_sale_valid_for_token(&sale, &token) {
+            return false;
+        }
+        // the owner may have listed the token again in the meantime
+        let contract_and_token_id = format!("{}{}{}", sale.nft_contract_id, DELIMETER, sale.token_id);
+        if self.sales.get(&contract_and_token_id).is_some() {
+            return false;
+        }
+
+        // charged to the owner like a new listing, if their deposit no longer covers it this panics
+        // and the token stays unlisted
+        let sale_created = SaleCreatedLog {
+            owner_id: sale.owner_id.clone(),
+            nft_contract_id: sale.nft_contract_id.clone(),
+            token_id: sale.token_id.clone(),
+            approval_id: sale.approval_id,
+            sale_conditions: sale.sale_conditions.clone(),
+            is_auction: sale.is_auction,
+            token_type: sale.token_type.clone(),
+            ends_at: sale.ends_at,
+        };
+        self.internal_add_sale(sale);
+        MarketEventKind::SaleCreated(vec![sale_created]).emit();
+        true
+    }
+
     #[private]
     pub fn resolve_purchase(
         &mut self,
-        ft_token_id: AccountId,
-        buyer_id: AccountId,
         sale: Sale,
-        price: U128,
+        purchase: Purchase,
+        market_fee: U128,
     ) -> U128 {
+        let Purchase {
+            ft_token_id,
+            buyer_id,
+            price,
+            escrowed,
+        } = purchase;
 
         // checking for payout information
-        let payout_option = promise_result_as_success().and_then(|value| {
-            // None means a bad payout from bad NFT contract
-            near_sdk::serde_json::from_slice::<Payout>(&value)
-                .ok()
-                .and_then(|payout| {
-                    // gas to do 10 FT transfers (and definitely 10 NEAR transfers)
-                    if payout.len() + sale.bids.len() > 10 || payout.is_empty() {
-                        env::log(format!("Cannot have more than 10 royalties and sale.bids refunds").as_bytes());
-                        None
-                    } else {
-                        // TODO off by 1 e.g. payouts are fractions of 3333 + 3333 + 3333
-                        let mut remainder = price.0;
-                        for &value in payout.values() {
-                            remainder = remainder.checked_sub(value.0)?;
-                        }
-                        if remainder == 0 || remainder == 1 {
-                            Some(payout)
-                        } else {
-                            None
-                        }
-                    }
-                })
-        });
-        // is payout option valid?
-        let payout = if let Some(payout_option) = payout_option {
-            payout_option
-        } else {
-            if ft_token_id == "near" {
-                Promise::new(buyer_id).transfer(u128::from(price));
+        let payout_result = payout_from_promise_result(price.0, market_fee.0, sale.bids.len());
+
+        // sale is gone either way, return all outstanding bids (accepted offer bid was already removed)
+        self.refund_all_bids(&sale);
+
+        // is payout valid?
+        let payout = match payout_result {
+            Ok(payout) => payout,
+            Err(reason) => {
+                // escrowed bids and NEAR are refunded from the market's balance, FTs sent with
+                // ft_transfer_call go back through ft_resolve_transfer
+                if escrowed || ft_token_id == "near" {
+                    transfer_token(&buyer_id, &ft_token_id, price.0);
+                }
+                // the token most likely never moved, list it again (without bids) if the seller still owns it.
+                // an ended timed auction couldn't take bids or settle again, its bids are refunded above
+                let auction_ended = sale.ends_at.map(|ends_at| now_ms() >= ends_at.0).unwrap_or(false);
+                if reason == PayoutFailureReason::TransferFailed && !auction_ended {
+                    let mut relist_sale = sale.clone();
+                    relist_sale.bids.clear();
+                    ext_contract::nft_token(
+                        sale.token_id.clone(),
+                        &sale.nft_contract_id,
+                        NO_DEPOSIT,
+                        GAS_FOR_NFT_TOKEN,
+                    )
+                    .then(ext_self::resolve_relist_sale(
+                        relist_sale,
+                        &env::current_account_id(),
+                        NO_DEPOSIT,
+                        GAS_FOR_RESOLVE_RELIST,
+                    ));
+                }
+                MarketEventKind::PayoutFailed(vec![PayoutFailedLog {
+                    buyer_id,
+                    seller_id: sale.owner_id,
+                    nft_contract_id: sale.nft_contract_id,
+                    token_id: sale.token_id,
+                    ft_token_id,
+                    price,
+                    reason,
+                }])
+                .emit();
+                // leave function and return all FTs in ft_resolve_transfer
+                return price;
             }
-            // leave function and return all FTs in ft_resolve_transfer
-            return price;
         };
-        // Going to payout everyone, first return all outstanding bids (accepted offer bid was already removed)
-        self.refund_all_bids(&sale.bids);
 
-        // NEAR payouts
+        MarketEventKind::PurchaseCompleted(vec![PurchaseLog {
+            buyer_id,
+            seller_id: sale.owner_id.clone(),
+            nft_contract_id: sale.nft_contract_id.clone(),
+            token_id: sale.token_id.clone(),
+            ft_token_id: ft_token_id.clone(),
+            price,
+            market_fee,
+            payout: payout.clone(),
+        }])
+        .emit();
+
+        self.internal_pay_out(&ft_token_id, payout, market_fee.0);
+
         if ft_token_id == "near" {
-            for (receiver_id, amount) in payout {
-                Promise::new(receiver_id).transfer(amount.0);
-            }
             // refund all FTs (won't be any)
             price
         } else {
-            // FT payouts
-            for (receiver_id, amount) in payout {
-                ext_contract::ft_transfer(
-                    receiver_id,
-                    amount,
-                    None,
-                    &ft_token_id,
-                    1,
-                    GAS_FOR_FT_TRANSFER,
-                );
-            }
             // keep all FTs (already transferred for payouts)
             U128(0)
         }
     }
+
+    /// escrowed offers (collection and token offers), pays the seller or refunds the buyer
+    #[private]
+    pub fn resolve_offer_purchase(&mut self, purchase: OfferPurchase, market_fee: U128) -> bool {
+        let OfferPurchase {
+            buyer_id,
+            seller_id,
+            nft_contract_id,
+            token_id,
+            ft_token_id,
+            price,
+        } = purchase;
+        match payout_from_promise_result(price.0, market_fee.0, 0) {
+            Ok(payout) => {
+                MarketEventKind::PurchaseCompleted(vec![PurchaseLog {
+                    buyer_id,
+                    seller_id,
+                    nft_contract_id,
+                    token_id,
+                    ft_token_id: ft_token_id.clone(),
+                    price,
+                    market_fee,
+                    payout: payout.clone(),
+                }])
+                .emit();
+                self.internal_pay_out(&ft_token_id, payout, market_fee.0);
+                true
+            }
+            Err(reason) => {
+                // the offer was already taken off the book, its escrow goes back to the buyer
+                transfer_token(&buyer_id, &ft_token_id, price.0);
+                MarketEventKind::PayoutFailed(vec![PayoutFailedLog {
+                    buyer_id,
+                    seller_id,
+                    nft_contract_id,
+                    token_id,
+                    ft_token_id,
+                    price,
+                    reason,
+                }])
+                .emit();
+                false
+            }
+        }
+    }
 }
 
 /// self call
 
 #[ext_contract(ext_self)]
 trait ExtSelf {
+    fn resolve_prune_sale(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: String,
+    ) -> Promise;
+    fn resolve_relist_sale(
+        &mut self,
+        sale: Sale,
+    ) -> Promise;
     fn resolve_purchase(
         &mut self,
-        ft_token_id: AccountId,
-        buyer_id: AccountId,
         sale: Sale,
-        price: U128,
+        purchase: Purchase,
+        market_fee: U128,
+    ) -> Promise;
+    fn resolve_offer_purchase(
+        &mut self,
+        purchase: OfferPurchase,
+        market_fee: U128,
+    ) -> Promise;
+    fn resolve_fill_collection_offer(
+        &mut self,
+        fill_offer: FillOffer,
     ) -> Promise;
 }
@@ -7,7 +7,7 @@ impl Contract {
     pub fn get_supply_sales(
         &self,
     ) -> U64 {
-        U64(self.sales.len())
+        U64(self.sales.len() + self.legacy_sales.len())
     }
     
     pub fn get_supply_by_owner_id(
@@ -39,7 +39,7 @@ impl Contract {
         let start = u64::from(from_index);
         let end = min(start + limit, sales.len());
         for i in start..end {
-            tmp.push(self.sales.get(&keys.get(i).unwrap()).unwrap());
+            tmp.push(self.internal_get_sale(&keys.get(i).unwrap()).unwrap());
         }
         tmp
     }
@@ -73,7 +73,7 @@ impl Contract {
         let start = u64::from(from_index);
         let end = min(start + limit, sales.len());
         for i in start..end {
-            tmp.push(self.sales.get(&format!("{}{}{}", &nft_contract_id, DELIMETER, &keys.get(i).unwrap())).unwrap());
+            tmp.push(self.internal_get_sale(&format!("{}{}{}", &nft_contract_id, DELIMETER, &keys.get(i).unwrap())).unwrap());
         }
         tmp
     }
@@ -107,13 +107,34 @@ impl Contract {
         let start = u64::from(from_index);
         let end = min(start + limit, sales.len());
         for i in start..end {
-            tmp.push(self.sales.get(&keys.get(i).unwrap()).unwrap());
+            tmp.push(self.internal_get_sale(&keys.get(i).unwrap()).unwrap());
         }
         tmp
     }
 
     pub fn get_sale(&self, nft_contract_token: ContractAndTokenId) -> Option<Sale> {
-        self.sales.get(&nft_contract_token)
+        self.internal_get_sale(&nft_contract_token)
+    }
+
+    /// price a buyer pays now, follows the schedule for dutch auctions
+    pub fn get_current_price(
+        &self,
+        nft_contract_id: AccountId,
+        token_id: String,
+        ft_token_id: AccountId,
+    ) -> U128 {
+        let sale = self
+            .sales
+            .get(&format!("{}{}{}", &nft_contract_id, DELIMETER, &token_id))
+            .expect("No sale");
+        if let Some(current_price) = sale.dutch_price(&ft_token_id) {
+            U128(current_price)
+        } else {
+            *sale
+                .sale_conditions
+                .get(&ft_token_id)
+                .expect("Not for sale in that token type")
+        }
     }
     
 }
@@ -0,0 +1,258 @@
+use crate::*;
+
+/// CUSTOM - escrowed offers on individual tokens that don't need to be listed, accepted by the owner through nft_approve
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenOffer {
+    pub owner_id: AccountId,
+    pub nft_contract_id: AccountId,
+    pub token_id: TokenId,
+    pub ft_token_id: FungibleTokenId,
+    pub price: U128,
+    /// ms, after this the offer can't be accepted and anyone can refund it
+    pub expires_at: U64,
+    pub created_at: U64,
+}
+
+/// one offer per offerer and token
+pub type TokenOffers = HashMap<AccountId, TokenOffer>;
+
+/// ft_transfer_call msg `{"token_offer": {...}}`, the whole amount is the offer
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenOfferArgs {
+    pub nft_contract_id: ValidAccountId,
+    pub token_id: TokenId,
+    pub expires_at: U64,
+}
+
+/// nft_approve msg `{"accept_offer": "<offerer account_id>"}`
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AcceptOfferArgs {
+    pub accept_offer: ValidAccountId,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// offer the attached NEAR for a token, FT offers go through ft_transfer_call with a token_offer msg
+    #[payable]
+    pub fn make_token_offer(
+        &mut self,
+        nft_contract_id: ValidAccountId,
+        token_id: TokenId,
+        expires_at: U64,
+    ) {
+        self.internal_add_token_offer(
+            env::predecessor_account_id(),
+            TokenOfferArgs {
+                nft_contract_id,
+                token_id,
+                expires_at,
+            },
+            "near".to_string(),
+            env::attached_deposit(),
+        );
+    }
+
+    #[payable]
+    pub fn cancel_token_offer(&mut self, nft_contract_id: ValidAccountId, token_id: TokenId) {
+        assert_one_yocto();
+        let offer = self
+            .internal_remove_token_offer(nft_contract_id.into(), token_id, env::predecessor_account_id())
+            .expect("No offer");
+        transfer_token(&offer.owner_id, &offer.ft_token_id, offer.price.0);
+    }
+
+    /// anyone can return expired offers on a token to their owners
+    pub fn refund_expired_token_offers(
+        &mut self,
+        nft_contract_id: ValidAccountId,
+        token_id: TokenId,
+    ) -> u64 {
+        let nft_contract_id: AccountId = nft_contract_id.into();
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMETER, token_id);
+        let now = now_ms();
+        let expired: Vec<AccountId> = self
+            .token_offers
+            .get(&contract_and_token_id)
+            .map(|offers| {
+                offers
+                    .values()
+                    .filter(|offer| offer.expires_at.0 <= now)
+                    .map(|offer| offer.owner_id.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        for owner_id in &expired {
+            let offer = self
+                .internal_remove_token_offer(nft_contract_id.clone(), token_id.clone(), owner_id.clone())
+                .unwrap();
+            transfer_token(&offer.owner_id, &offer.ft_token_id, offer.price.0);
+        }
+        expired.len() as u64
+    }
+
+    /// views
+
+    pub fn get_token_offers(&self, nft_contract_id: AccountId, token_id: TokenId) -> Vec<TokenOffer> {
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMETER, token_id);
+        self.token_offers
+            .get(&contract_and_token_id)
+            .map(|offers| offers.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn get_supply_token_offers_by_owner_id(&self, account_id: AccountId) -> U64 {
+        U64(self.token_offers_by_owner_id.get(&account_id).map(|offers| offers.len()).unwrap_or(0))
+    }
+
+    pub fn get_token_offers_by_owner_id(
+        &self,
+        account_id: AccountId,
+        from_index: U64,
+        limit: u64,
+    ) -> Vec<TokenOffer> {
+        let by_owner_id = if let Some(by_owner_id) = self.token_offers_by_owner_id.get(&account_id) {
+            by_owner_id
+        } else {
+            return vec![];
+        };
+        let keys = by_owner_id.as_vector();
+        let start = u64::from(from_index);
+        let end = min(start + limit, keys.len());
+        (start..end)
+            .map(|i| {
+                self.token_offers
+                    .get(&keys.get(i).unwrap())
+                    .unwrap()
+                    .remove(&account_id)
+                    .unwrap()
+            })
+            .collect()
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_add_token_offer(
+        &mut self,
+        owner_id: AccountId,
+        args: TokenOfferArgs,
+        ft_token_id: FungibleTokenId,
+        price: Balance,
+    ) {
+        let TokenOfferArgs {
+            nft_contract_id,
+            token_id,
+            expires_at,
+        } = args;
+        let nft_contract_id: AccountId = nft_contract_id.into();
+        assert!(price > 0, "Offer must be greater than 0");
+        assert!(expires_at.0 > now_ms(), "expires_at must be in the future");
+        self.assert_ft_token_accepted(&ft_token_id);
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMETER, token_id);
+        assert!(
+            self.sales.get(&contract_and_token_id).is_none(),
+            "Token is listed, use offer or ft_transfer_call on the sale"
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        let mut offers = self.token_offers.get(&contract_and_token_id).unwrap_or_default();
+        assert!(
+            !offers.contains_key(&owner_id),
+            "Offer already made, cancel it first"
+        );
+        let offer = TokenOffer {
+            owner_id: owner_id.clone(),
+            nft_contract_id,
+            token_id,
+            ft_token_id,
+            price: U128(price),
+            expires_at,
+            created_at: U64(now_ms()),
+        };
+        offers.insert(owner_id.clone(), offer.clone());
+        self.token_offers.insert(&contract_and_token_id, &offers);
+
+        let mut by_owner_id = self.token_offers_by_owner_id.get(&owner_id).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::TokenOffersByOwnerIdInner {
+                    account_id_hash: hash_account_id(&owner_id),
+                }
+                .try_to_vec()
+                .unwrap(),
+            )
+        });
+        by_owner_id.insert(&contract_and_token_id);
+        self.token_offers_by_owner_id.insert(&owner_id, &by_owner_id);
+
+        self.internal_charge_storage(&owner_id, initial_storage_usage);
+
+        MarketEventKind::TokenOfferCreated(vec![offer]).emit();
+    }
+
+    /// takes the offer off the book, escrow is left to the caller
+    pub(crate) fn internal_remove_token_offer(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        owner_id: AccountId,
+    ) -> Option<TokenOffer> {
+        let initial_storage_usage = env::storage_usage();
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMETER, token_id);
+        let mut offers = self.token_offers.get(&contract_and_token_id)?;
+        let offer = offers.remove(&owner_id)?;
+        if offers.is_empty() {
+            self.token_offers.remove(&contract_and_token_id);
+        } else {
+            self.token_offers.insert(&contract_and_token_id, &offers);
+        }
+
+        let mut by_owner_id = self.token_offers_by_owner_id.get(&owner_id).expect("No offer by owner_id");
+        by_owner_id.remove(&contract_and_token_id);
+        if by_owner_id.is_empty() {
+            self.token_offers_by_owner_id.remove(&owner_id);
+        } else {
+            self.token_offers_by_owner_id.insert(&owner_id, &by_owner_id);
+        }
+
+        self.internal_charge_storage(&owner_id, initial_storage_usage);
+
+        MarketEventKind::TokenOfferRemoved(vec![TokenOfferRemovedLog {
+            owner_id,
+            nft_contract_id,
+            token_id,
+        }])
+        .emit();
+
+        Some(offer)
+    }
+
+    /// token owner approved the market with an accept_offer msg
+    pub(crate) fn internal_accept_token_offer(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        owner_id: AccountId,
+        approval_id: u64,
+        args: AcceptOfferArgs,
+    ) -> Promise {
+        let offer = self
+            .internal_remove_token_offer(nft_contract_id.clone(), token_id.clone(), args.accept_offer.into())
+            .expect("No offer");
+        assert_ne!(offer.owner_id, owner_id, "Cannot accept your own offer");
+        assert!(offer.expires_at.0 > now_ms(), "Offer expired");
+        self.internal_escrow_purchase(
+            OfferPurchase {
+                buyer_id: offer.owner_id,
+                seller_id: owner_id,
+                nft_contract_id,
+                token_id,
+                ft_token_id: offer.ft_token_id,
+                price: offer.price,
+            },
+            approval_id,
+        )
+    }
+}
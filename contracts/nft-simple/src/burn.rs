@@ -1,14 +1,55 @@
 use crate::*;
 
-/// CUSTOM - owner can burn a locked token for a given user, reducing the enumerable->nft_supply_for_type
+/// CUSTOM - burn a token, reducing the enumerable->nft_supply_for_type
+/// token owner can burn unlocked (or untyped) tokens, contract owner can burn a locked token for a given user
 #[near_bindgen]
 impl Contract {
     #[payable]
     pub fn nft_burn(
         &mut self,
-        token_id: Option<TokenId>,
+        token_id: TokenId,
     ) {
-        assert_eq!(self.is_token_locked(token_id), true, "Token must be locked");
-        //TODO burn token
+        assert_one_yocto();
+        let token = self.tokens_by_id.get(&token_id).expect("No token");
+        let predecessor_account_id = env::predecessor_account_id();
+
+        let is_locked = token
+            .token_type
+            .as_ref()
+            .map(|token_type| self.is_token_type_locked(token_type))
+            .unwrap_or(false);
+        if is_locked {
+            assert_eq!(&predecessor_account_id, &self.owner_id, "Only contract owner can burn a locked token");
+        } else {
+            assert_eq!(&predecessor_account_id, &token.owner_id, "Only token owner can burn a token");
+        }
+
+        let initial_storage_usage = env::storage_usage();
+        self.internal_burn(&token_id, &token);
+        let storage_released = initial_storage_usage - env::storage_usage();
+
+        NftEventKind::NftBurn(vec![NftBurnLog {
+            authorized_id: if predecessor_account_id != token.owner_id {
+                Some(predecessor_account_id)
+            } else {
+                None
+            },
+            owner_id: token.owner_id.clone(),
+            token_ids: vec![token_id],
+            memo: None,
+        }])
+        .emit();
+
+        // approvals are refunded at their own rate, the rest of the released storage goes back to the token owner
+        let approvals_storage: u64 = token
+            .approved_account_ids
+            .keys()
+            .map(bytes_for_approved_account_id)
+            .sum();
+        refund_approved_account_ids(token.owner_id.clone(), &token.approved_account_ids);
+        let refund = Balance::from(storage_released.saturating_sub(approvals_storage)) * env::storage_byte_cost();
+        if refund > 0 {
+            Promise::new(token.owner_id).transfer(refund);
+        }
     }
-}
\ No newline at end of file
+}
@@ -9,7 +9,7 @@ impl Contract {
         keys.iter()
             .skip(start as usize)
             .take(limit.unwrap_or(0) as usize)
-            .map(|token_id| self.nft_token(token_id.clone()).unwrap())
+            .map(|token_id| self.nft_token(token_id).unwrap())
             .collect()
     }
 
@@ -18,8 +18,8 @@ impl Contract {
         token_ids: Vec<String>,
     ) -> Vec<JsonToken> {
         let mut tmp = vec![];
-        for i in 0..token_ids.len() {
-            tmp.push(self.nft_token(token_ids[i].clone()).unwrap());
+        for token_id in token_ids {
+            tmp.push(self.nft_token(token_id).unwrap());
         }
         tmp
     }
@@ -28,7 +28,7 @@ impl Contract {
         &self,
         token_type: &String,
     ) -> U64 {
-        let tokens_per_type = self.tokens_per_type.get(&token_type);
+        let tokens_per_type = self.tokens_per_type.get(token_type);
         if let Some(tokens_per_type) = tokens_per_type {
             U64(tokens_per_type.len())
         } else {
@@ -87,7 +87,7 @@ impl Contract {
         keys.iter()
             .skip(start as usize)
             .take(limit.unwrap_or(0) as usize)
-            .map(|token_id| self.nft_token(token_id.clone()).unwrap())
+            .map(|token_id| self.nft_token(token_id).unwrap())
             .collect()
     }
 }
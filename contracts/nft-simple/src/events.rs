@@ -0,0 +1,76 @@
+//! CUSTOM - NEP-297 nft_mint, nft_transfer and nft_burn events
+
+use crate::*;
+
+pub const NFT_STANDARD_NAME: &str = "nep171";
+pub const NFT_STANDARD_VERSION: &str = "1.0.0";
+const EVENT_JSON_PREFIX: &str = "EVENT_JSON:";
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+pub enum NftEventKind {
+    NftMint(Vec<NftMintLog>),
+    NftTransfer(Vec<NftTransferLog>),
+    NftBurn(Vec<NftBurnLog>),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftEvent {
+    pub standard: String,
+    pub version: String,
+    #[serde(flatten)]
+    pub event: NftEventKind,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftMintLog {
+    pub owner_id: AccountId,
+    pub token_ids: Vec<TokenId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftTransferLog {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authorized_id: Option<AccountId>,
+    pub old_owner_id: AccountId,
+    pub new_owner_id: AccountId,
+    pub token_ids: Vec<TokenId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftBurnLog {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authorized_id: Option<AccountId>,
+    pub owner_id: AccountId,
+    pub token_ids: Vec<TokenId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+impl NftEventKind {
+    pub(crate) fn emit(self) {
+        let event = NftEvent {
+            standard: NFT_STANDARD_NAME.to_string(),
+            version: NFT_STANDARD_VERSION.to_string(),
+            event: self,
+        };
+        env::log(
+            format!(
+                "{}{}",
+                EVENT_JSON_PREFIX,
+                near_sdk::serde_json::to_string(&event).unwrap()
+            )
+            .as_bytes(),
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use crate::*;
-use near_sdk::{log, CryptoHash};
+use near_sdk::CryptoHash;
 use std::mem::size_of;
 
 pub(crate) fn royalty_to_payout(a: u32, b: Balance) -> U128 {
@@ -12,6 +12,11 @@ pub(crate) fn hash_account_id(account_id: &AccountId) -> CryptoHash {
     hash
 }
 
+/// unlock and mint times are in ms, like the market's sales
+pub(crate) fn now_ms() -> u64 {
+    env::block_timestamp() / 1_000_000
+}
+
 pub(crate) fn assert_one_yocto() {
     assert_eq!(
         env::attached_deposit(),
@@ -43,6 +48,22 @@ pub(crate) fn refund_deposit(storage_used: u64) {
     }
 }
 
+/// CUSTOM - royalty map from the minter's perpetual_royalties (percentage paid with every transfer)
+pub(crate) fn royalty_from_perpetual(perpetual_royalties: Option<HashMap<AccountId, u32>>) -> HashMap<AccountId, u32> {
+    let mut royalty = HashMap::new();
+    let mut total_perpetual = 0;
+    if let Some(perpetual_royalties) = perpetual_royalties {
+        assert!(perpetual_royalties.len() < 7, "Cannot add more than 6 perpetual royalty amounts");
+        for (account, amount) in perpetual_royalties {
+            royalty.insert(account, amount);
+            total_perpetual += amount;
+        }
+    }
+    // royalty limit for minter capped at 20%
+    assert!(total_perpetual <= MINTER_ROYALTY_CAP, "Perpetual royalties cannot be more than 20%");
+    royalty
+}
+
 // TODO: need a way for end users to determine how much an approval will cost.
 pub(crate) fn bytes_for_approved_account_id(account_id: &AccountId) -> u64 {
     // The extra 4 bytes are coming from Borsh serialization to store the length of the string.
@@ -86,7 +107,7 @@ impl Contract {
         let mut tokens_set = self.tokens_per_owner.get(account_id).unwrap_or_else(|| {
             UnorderedSet::new(
                 StorageKey::TokenPerOwnerInner {
-                    account_id_hash: hash_account_id(&account_id),
+                    account_id_hash: hash_account_id(account_id),
                 }
                 .try_to_vec()
                 .unwrap(),
@@ -113,6 +134,29 @@ impl Contract {
         }
     }
 
+    pub(crate) fn internal_burn(
+        &mut self,
+        token_id: &TokenId,
+        token: &Token,
+    ) {
+        self.internal_remove_token_from_owner(&token.owner_id, token_id);
+        self.tokens_by_id.remove(token_id);
+        self.token_metadata_by_id.remove(token_id);
+
+        if let Some(token_type) = &token.token_type {
+            let mut tokens_per_type = self
+                .tokens_per_type
+                .get(token_type)
+                .expect("Token should be in tokens_per_type");
+            tokens_per_type.remove(token_id);
+            if tokens_per_type.is_empty() {
+                self.tokens_per_type.remove(token_type);
+            } else {
+                self.tokens_per_type.insert(token_type, &tokens_per_type);
+            }
+        }
+    }
+
     pub(crate) fn internal_transfer(
         &mut self,
         sender_id: &AccountId,
@@ -123,9 +167,9 @@ impl Contract {
     ) -> Token {
         let token = self.tokens_by_id.get(token_id).expect("No token");
 
-        // CUSTOM - token_type can be locked until unlocked by owner
-        if token.token_type.is_some() {
-            assert_eq!(self.token_types_locked.contains(&token.token_type.clone().unwrap()), false, "Token transfers are locked");
+        // CUSTOM - token_type can be locked until unlocked by owner or a scheduled unlock passes
+        if let Some(token_type) = &token.token_type {
+            assert!(!self.is_token_type_locked(token_type), "Token transfers are locked");
         }
 
         
@@ -154,13 +198,6 @@ impl Contract {
             "The token owner and the receiver should be different"
         );
 
-        log!(
-            "Transfer {} from @{} to @{}",
-            token_id,
-            &token.owner_id,
-            receiver_id
-        );
-
         self.internal_remove_token_from_owner(&token.owner_id, token_id);
         self.internal_add_token_to_owner(receiver_id, token_id);
 
@@ -173,9 +210,18 @@ impl Contract {
         };
         self.tokens_by_id.insert(token_id, &new_token);
 
-        if let Some(memo) = memo {
-            env::log(format!("Memo: {}", memo).as_bytes());
-        }
+        NftEventKind::NftTransfer(vec![NftTransferLog {
+            authorized_id: if sender_id != &token.owner_id {
+                Some(sender_id.clone())
+            } else {
+                None
+            },
+            old_owner_id: token.owner_id.clone(),
+            new_owner_id: receiver_id.clone(),
+            token_ids: vec![token_id.clone()],
+            memo,
+        }])
+        .emit();
 
         token
     }
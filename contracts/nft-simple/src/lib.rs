@@ -10,27 +10,47 @@ use near_sdk::{
 };
 
 use crate::internal::*;
+pub use crate::burn::*;
+pub use crate::events::*;
 pub use crate::metadata::*;
+pub use crate::migrate::*;
 pub use crate::mint::*;
 pub use crate::nft_core::*;
+pub use crate::paid_mint::*;
+pub use crate::roles::*;
+pub use crate::series::*;
 pub use crate::token::*;
 pub use crate::enumerable::*;
 
+mod burn;
+mod events;
 mod internal;
 mod metadata;
+mod migrate;
 mod mint;
 mod nft_core;
+mod paid_mint;
+mod roles;
+mod series;
 mod token;
 mod enumerable;
 
 // CUSTOM types
 pub type TokenType = String;
 pub type TypeSupplyCaps = HashMap<TokenType, U64>;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ScheduledUnlock {
+    pub token_type: TokenType,
+    pub unlock_at: U64,
+}
 pub const CONTRACT_ROYALTY_CAP: u32 = 1000;
 pub const MINTER_ROYALTY_CAP: u32 = 2000;
 
 near_sdk::setup_alloc!();
 
+/// Changing this layout requires bumping CONTRACT_VERSION and a migration path, see migrate.rs
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct Contract {
@@ -52,6 +72,23 @@ pub struct Contract {
     pub tokens_per_type: LookupMap<TokenType, UnorderedSet<TokenId>>,
     pub token_types_locked: UnorderedSet<TokenType>,
     pub contract_royalty: u32,
+    /// CUSTOM - accounts that manage token types alongside the owner
+    pub admins: UnorderedSet<AccountId>,
+    /// CUSTOM - locked token types that unlock once the block timestamp (ms) passes the value
+    pub scheduled_unlocks: UnorderedMap<TokenType, u64>,
+    /// CUSTOM - accounts that can mint any token_type, or only the token_types they were granted
+    pub minters: UnorderedSet<AccountId>,
+    pub minters_per_type: LookupMap<TokenType, UnorderedSet<AccountId>>,
+    pub untyped_mints_open: bool,
+    /// CUSTOM - paid public mints by token_type, proceeds by FT ("near" for NEAR)
+    pub mint_configs: LookupMap<TokenType, MintConfig>,
+    pub paid_mints_per_account: LookupMap<String, u32>,
+    pub mint_proceeds: LookupMap<AccountId, Balance>,
+    /// CUSTOM - metadata templates shared by editions
+    pub series: UnorderedMap<SeriesId, Series>,
+    pub next_series_id: SeriesId,
+    /// CUSTOM - next auto token id, only goes up so burned ids aren't reissued
+    pub next_token_id: u64,
 }
 
 /// Helper structure to for keys of the persistent collections.
@@ -65,12 +102,21 @@ pub enum StorageKey {
     TokensPerType,
     TokensPerTypeInner { token_type_hash: CryptoHash },
     TokenTypesLocked,
+    Admins,
+    ScheduledUnlocks,
+    Minters,
+    MintersPerType,
+    MintersPerTypeInner { token_type_hash: CryptoHash },
+    MintConfigs,
+    PaidMintsPerAccount,
+    MintProceeds,
+    Series,
 }
 
 #[near_bindgen]
 impl Contract {
     #[init]
-    pub fn new(owner_id: ValidAccountId, metadata: NFTMetadata, supply_cap_by_type: TypeSupplyCaps, locked: Option<bool>) -> Self {
+    pub fn new(owner_id: ValidAccountId, metadata: NFTMetadata, supply_cap_by_type: TypeSupplyCaps, locked: Option<bool>, untyped_mints_open: Option<bool>) -> Self {
         let mut this = Self {
             tokens_per_owner: LookupMap::new(StorageKey::TokensPerOwner.try_to_vec().unwrap()),
             tokens_by_id: LookupMap::new(StorageKey::TokensById.try_to_vec().unwrap()),
@@ -87,16 +133,28 @@ impl Contract {
             tokens_per_type: LookupMap::new(StorageKey::TokensPerType.try_to_vec().unwrap()),
             token_types_locked: UnorderedSet::new(StorageKey::TokenTypesLocked.try_to_vec().unwrap()),
             contract_royalty: 0,
+            admins: UnorderedSet::new(StorageKey::Admins.try_to_vec().unwrap()),
+            scheduled_unlocks: UnorderedMap::new(StorageKey::ScheduledUnlocks.try_to_vec().unwrap()),
+            minters: UnorderedSet::new(StorageKey::Minters.try_to_vec().unwrap()),
+            minters_per_type: LookupMap::new(StorageKey::MintersPerType.try_to_vec().unwrap()),
+            untyped_mints_open: untyped_mints_open.unwrap_or(false),
+            mint_configs: LookupMap::new(StorageKey::MintConfigs.try_to_vec().unwrap()),
+            paid_mints_per_account: LookupMap::new(StorageKey::PaidMintsPerAccount.try_to_vec().unwrap()),
+            mint_proceeds: LookupMap::new(StorageKey::MintProceeds.try_to_vec().unwrap()),
+            series: UnorderedMap::new(StorageKey::Series.try_to_vec().unwrap()),
+            next_series_id: 1,
+            next_token_id: 1,
         };
 
         if locked.unwrap_or(false) {
             // CUSTOM - tokens are locked by default
             for token_type in this.supply_cap_by_type.keys() {
-                this.token_types_locked.insert(&token_type);
+                this.token_types_locked.insert(token_type);
             }
         }
 
         this.measure_min_token_storage_cost();
+        write_contract_version();
 
         this
     }
@@ -134,18 +192,39 @@ impl Contract {
         self.assert_owner();
         for (token_type, hard_cap) in &supply_cap_by_type {
             if locked.unwrap_or(false) {
-                assert!(self.token_types_locked.insert(&token_type), "Token type should not be locked");
+                assert!(self.token_types_locked.insert(token_type), "Token type should not be locked");
             }
             assert!(self.supply_cap_by_type.insert(token_type.to_string(), *hard_cap).is_none(), "Token type exists");
         }
     }
 
+    /// owner or admin, locking again drops any scheduled unlock
+    pub fn lock_token_types(&mut self, token_types: Vec<String>) {
+        self.assert_owner_or_admin();
+        for token_type in &token_types {
+            assert!(self.supply_cap_by_type.contains_key(token_type), "No token type {}", token_type);
+            self.token_types_locked.insert(token_type);
+            self.scheduled_unlocks.remove(token_type);
+        }
+    }
+
+    /// owner or admin
     pub fn unlock_token_types(&mut self, token_types: Vec<String>) {
+        self.assert_owner_or_admin();
         for token_type in &token_types {
-            self.token_types_locked.remove(&token_type);
+            self.token_types_locked.remove(token_type);
+            self.scheduled_unlocks.remove(token_type);
         }
     }
 
+    /// owner or admin, transfers of a locked token_type open once the block timestamp passes unlock_at (ms)
+    pub fn schedule_unlock(&mut self, token_type: TokenType, unlock_at: U64) {
+        self.assert_owner_or_admin();
+        assert!(self.token_types_locked.contains(&token_type), "Token type is not locked");
+        assert!(unlock_at.0 > now_ms(), "unlock_at must be in the future");
+        self.scheduled_unlocks.insert(&token_type, &unlock_at.0);
+    }
+
     /// CUSTOM - views
 
     pub fn get_contract_royalty(&self) -> u32 {
@@ -156,14 +235,769 @@ impl Contract {
         self.supply_cap_by_type.clone()
     }
 
+    /// types whose scheduled unlock has passed are not listed
     pub fn get_token_types_locked(&self) -> Vec<String> {
-        self.token_types_locked.to_vec()
+        self.token_types_locked
+            .iter()
+            .filter(|token_type| self.is_token_type_locked(token_type))
+            .collect()
+    }
+
+    /// unlocks that haven't passed yet
+    pub fn get_scheduled_unlocks(&self) -> Vec<ScheduledUnlock> {
+        let now = now_ms();
+        self.scheduled_unlocks
+            .iter()
+            .filter(|(_, unlock_at)| *unlock_at > now)
+            .map(|(token_type, unlock_at)| ScheduledUnlock {
+                token_type,
+                unlock_at: U64(unlock_at),
+            })
+            .collect()
     }
 
     pub fn is_token_locked(&self, token_id: TokenId) -> bool {
         let token = self.tokens_by_id.get(&token_id).expect("No token");
         assert!(token.token_type.is_some(), "Token must have type");
         let token_type = token.token_type.unwrap();
-        self.token_types_locked.contains(&token_type)
+        self.is_token_type_locked(&token_type)
+    }
+}
+
+impl Contract {
+    /// locked until a scheduled unlock passes, no write needed when it does
+    pub(crate) fn is_token_type_locked(&self, token_type: &TokenType) -> bool {
+        if !self.token_types_locked.contains(token_type) {
+            return false;
+        }
+        match self.scheduled_unlocks.get(token_type) {
+            Some(unlock_at) => now_ms() < unlock_at,
+            None => true,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod non_fungible_token_tests {
+    use near_sdk::MockedBlockchain;
+    use near_sdk::test_utils::{accounts, get_created_receipts, get_logs, VMContextBuilder};
+    use std::convert::TryFrom;
+    use near_sdk::testing_env;
+
+    use super::*;
+
+    const MINT_STORAGE_COST: Balance = 100_000_000_000_000_000_000_000;
+
+    fn get_context(predecessor_account_id: ValidAccountId) -> VMContextBuilder {
+        let mut builder = VMContextBuilder::new();
+        builder
+            .current_account_id(accounts(0))
+            .signer_account_id(predecessor_account_id.clone())
+            .predecessor_account_id(predecessor_account_id);
+        builder
+    }
+
+    fn sample_metadata() -> NFTMetadata {
+        NFTMetadata {
+            spec: "nft-1.0.0".to_string(),
+            name: "Test".to_string(),
+            symbol: "TEST".to_string(),
+            icon: None,
+            base_uri: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    fn sample_token_metadata() -> TokenMetadata {
+        TokenMetadata {
+            title: Some("Token".to_string()),
+            description: None,
+            media: None,
+            media_hash: None,
+            copies: None,
+            issued_at: None,
+            expires_at: None,
+            starts_at: None,
+            updated_at: None,
+            extra: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    fn setup_contract(locked: bool) -> Contract {
+        testing_env!(get_context(accounts(0)).build());
+        let mut supply_cap_by_type = HashMap::new();
+        supply_cap_by_type.insert("A".to_string(), U64(10));
+        Contract::new(accounts(0), sample_metadata(), supply_cap_by_type, Some(locked), None)
+    }
+
+    fn mint(contract: &mut Contract, receiver_id: ValidAccountId, token_type: Option<TokenType>) -> TokenId {
+        testing_env!(get_context(accounts(0))
+            .attached_deposit(MINT_STORAGE_COST)
+            .build());
+        contract.nft_mint(None, sample_token_metadata(), None, Some(receiver_id), token_type);
+        contract.nft_total_supply().0.to_string()
+    }
+
+    #[test]
+    fn owner_burns_unlocked_token() {
+        let mut contract = setup_contract(false);
+        let token_id = mint(&mut contract, accounts(1), Some("A".to_string()));
+        assert_eq!(contract.nft_supply_for_type(&"A".to_string()).0, 1);
+
+        testing_env!(get_context(accounts(1)).attached_deposit(1).build());
+        contract.nft_burn(token_id.clone());
+
+        assert!(contract.nft_token(token_id).is_none());
+        assert_eq!(contract.nft_total_supply().0, 0);
+        assert_eq!(contract.nft_supply_for_owner(accounts(1).into()).0, 0);
+        assert_eq!(contract.nft_supply_for_type(&"A".to_string()).0, 0);
+    }
+
+    #[test]
+    fn burned_token_ids_are_not_reissued() {
+        let mut contract = setup_contract(false);
+        mint(&mut contract, accounts(1), None);
+        let token_id = mint(&mut contract, accounts(1), None);
+        assert_eq!(token_id, "2");
+
+        testing_env!(get_context(accounts(1)).attached_deposit(1).build());
+        contract.nft_burn(token_id.clone());
+        testing_env!(get_context(accounts(0)).attached_deposit(MINT_STORAGE_COST).build());
+        contract.nft_mint(Some("4".to_string()), sample_token_metadata(), None, Some(accounts(1)), None);
+        contract.nft_mint(None, sample_token_metadata(), None, Some(accounts(1)), None);
+        contract.nft_mint(None, sample_token_metadata(), None, Some(accounts(1)), None);
+
+        assert!(contract.nft_token(token_id).is_none());
+        assert!(contract.nft_token("3".to_string()).is_some());
+        assert!(contract.nft_token("5".to_string()).is_some());
+    }
+
+    #[test]
+    fn contract_owner_burns_locked_token() {
+        let mut contract = setup_contract(true);
+        let token_id = mint(&mut contract, accounts(1), Some("A".to_string()));
+
+        testing_env!(get_context(accounts(0)).attached_deposit(1).build());
+        contract.nft_burn(token_id.clone());
+
+        assert!(contract.nft_token(token_id).is_none());
+        assert_eq!(contract.nft_supply_for_owner(accounts(1).into()).0, 0);
+        assert_eq!(contract.nft_supply_for_type(&"A".to_string()).0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only contract owner can burn a locked token")]
+    fn token_owner_cannot_burn_locked_token() {
+        let mut contract = setup_contract(true);
+        let token_id = mint(&mut contract, accounts(1), Some("A".to_string()));
+
+        testing_env!(get_context(accounts(1)).attached_deposit(1).build());
+        contract.nft_burn(token_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only token owner can burn a token")]
+    fn stranger_cannot_burn_unlocked_token() {
+        let mut contract = setup_contract(false);
+        let token_id = mint(&mut contract, accounts(1), None);
+
+        testing_env!(get_context(accounts(2)).attached_deposit(1).build());
+        contract.nft_burn(token_id);
+    }
+
+    #[test]
+    fn mint_logs_nep297_event() {
+        let mut contract = setup_contract(false);
+        mint(&mut contract, accounts(1), None);
+        assert_eq!(
+            get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_mint","data":[{"owner_id":"bob","token_ids":["1"]}]}"#]
+        );
+    }
+
+    fn approve(contract: &mut Contract, token_id: &TokenId, account_ids: &[ValidAccountId]) {
+        for account_id in account_ids {
+            testing_env!(get_context(accounts(1)).attached_deposit(MINT_STORAGE_COST).build());
+            contract.nft_approve(token_id.clone(), account_id.clone(), None);
+        }
+    }
+
+    fn revoke_notifications() -> usize {
+        near_sdk::serde_json::to_string(&get_created_receipts())
+            .unwrap()
+            .matches("nft_on_revoke")
+            .count()
+    }
+
+    #[test]
+    fn revoke_notifies_only_when_asked() {
+        let mut contract = setup_contract(false);
+        let token_id = mint(&mut contract, accounts(1), None);
+        approve(&mut contract, &token_id, &[accounts(2), accounts(3)]);
+
+        testing_env!(get_context(accounts(1)).attached_deposit(1).build());
+        contract.nft_revoke(token_id.clone(), accounts(2), None);
+        assert_eq!(revoke_notifications(), 0);
+
+        testing_env!(get_context(accounts(1)).attached_deposit(1).build());
+        contract.nft_revoke(token_id.clone(), accounts(3), Some(true));
+        assert_eq!(revoke_notifications(), 1);
+        assert!(contract.nft_token(token_id).unwrap().approved_account_ids.is_empty());
+    }
+
+    #[test]
+    fn revoke_all_notifies_only_when_asked() {
+        let mut contract = setup_contract(false);
+        let token_id = mint(&mut contract, accounts(1), None);
+        approve(&mut contract, &token_id, &[accounts(2), accounts(3)]);
+
+        testing_env!(get_context(accounts(1)).attached_deposit(1).build());
+        contract.nft_revoke_all(token_id.clone(), None);
+        assert_eq!(revoke_notifications(), 0);
+        assert!(contract.nft_token(token_id.clone()).unwrap().approved_account_ids.is_empty());
+
+        approve(&mut contract, &token_id, &[accounts(2), accounts(3)]);
+        testing_env!(get_context(accounts(1)).attached_deposit(1).build());
+        contract.nft_revoke_all(token_id, Some(true));
+        assert_eq!(revoke_notifications(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot notify more than")]
+    fn revoke_all_caps_notifications() {
+        let mut contract = setup_contract(false);
+        let token_id = mint(&mut contract, accounts(1), None);
+        let account_ids: Vec<ValidAccountId> = (0..=10)
+            .map(|i| ValidAccountId::try_from(format!("spender{}.near", i)).unwrap())
+            .collect();
+        approve(&mut contract, &token_id, &account_ids);
+
+        testing_env!(get_context(accounts(1)).attached_deposit(1).build());
+        contract.nft_revoke_all(token_id, Some(true));
+    }
+
+    #[test]
+    fn revoke_all_notifies_up_to_the_cap() {
+        let mut contract = setup_contract(false);
+        let token_id = mint(&mut contract, accounts(1), None);
+        let account_ids: Vec<ValidAccountId> = (0..10)
+            .map(|i| ValidAccountId::try_from(format!("spender{}.near", i)).unwrap())
+            .collect();
+        approve(&mut contract, &token_id, &account_ids);
+
+        testing_env!(get_context(accounts(1)).attached_deposit(1).build());
+        contract.nft_revoke_all(token_id.clone(), Some(true));
+        assert_eq!(revoke_notifications(), 10);
+        // every notification gets its own fixed gas, the revoke doesn't wait for them
+        let receipts = near_sdk::serde_json::to_string(&get_created_receipts()).unwrap();
+        assert_eq!(receipts.matches("\"gas\":10000000000000").count(), 10);
+        assert!(contract.nft_token(token_id).unwrap().approved_account_ids.is_empty());
+    }
+
+    fn write_v1_snapshot(contract: Contract) {
+        // V1 fields written without a version key is what deployments before versioning look like
+        let snapshot = ContractV1 {
+            tokens_per_owner: contract.tokens_per_owner,
+            tokens_by_id: contract.tokens_by_id,
+            token_metadata_by_id: contract.token_metadata_by_id,
+            owner_id: contract.owner_id,
+            extra_storage_in_bytes_per_token: contract.extra_storage_in_bytes_per_token,
+            metadata: contract.metadata,
+            supply_cap_by_type: contract.supply_cap_by_type,
+            tokens_per_type: contract.tokens_per_type,
+            token_types_locked: contract.token_types_locked,
+            contract_royalty: contract.contract_royalty,
+        };
+        env::state_write(&snapshot);
+        env::storage_remove(b"VERSION");
+    }
+
+    #[test]
+    fn migrate_from_v1_snapshot() {
+        let mut contract = setup_contract(true);
+        let token_id = mint(&mut contract, accounts(1), Some("A".to_string()));
+        testing_env!(get_context(accounts(0)).build());
+        contract.set_contract_royalty(500);
+        write_v1_snapshot(contract);
+
+        testing_env!(get_context(accounts(0)).build());
+        let contract = Contract::migrate();
+
+        assert_eq!(contract.contract_version(), CONTRACT_VERSION);
+        assert_eq!(contract.owner_id, accounts(0).to_string());
+        assert_eq!(contract.get_contract_royalty(), 500);
+        assert_eq!(contract.get_token_types_locked(), vec!["A".to_string()]);
+        assert_eq!(contract.nft_supply_for_type(&"A".to_string()).0, 1);
+        assert_eq!(contract.nft_token(token_id).unwrap().owner_id, accounts(1).to_string());
+        assert!(contract.get_admins().is_empty());
+        assert!(contract.get_scheduled_unlocks().is_empty());
+        assert!(contract.get_untyped_mints_open());
+    }
+
+    #[test]
+    fn migrate_from_v7_converts_unlock_times_to_ms() {
+        let mut contract = setup_contract(true);
+        contract.scheduled_unlocks.insert(&"A".to_string(), &1_000_000_000);
+        let mut config = sample_mint_config();
+        config.starts_at = Some(U64(2_000_000_000));
+        contract.mint_configs.insert(&"A".to_string(), &config);
+        env::state_write(&contract);
+        env::storage_write(b"VERSION", &7u32.try_to_vec().unwrap());
+
+        testing_env!(get_context(accounts(0)).build());
+        let contract = Contract::migrate();
+        assert_eq!(contract.get_scheduled_unlocks()[0].unlock_at.0, 1_000);
+        assert_eq!(contract.get_mint_config("A".to_string()).unwrap().starts_at.unwrap().0, 2_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Owner's method")]
+    fn migrate_requires_owner() {
+        let contract = setup_contract(false);
+        write_v1_snapshot(contract);
+
+        testing_env!(get_context(accounts(1)).build());
+        Contract::migrate();
+    }
+
+    #[test]
+    fn mint_after_burn_skips_taken_token_id() {
+        let mut contract = setup_contract(false);
+        mint(&mut contract, accounts(1), None);
+        mint(&mut contract, accounts(1), None);
+
+        testing_env!(get_context(accounts(1)).attached_deposit(1).build());
+        contract.nft_burn("1".to_string());
+
+        mint(&mut contract, accounts(1), None);
+        assert_eq!(contract.nft_supply_for_owner(accounts(1).into()).0, 2);
+        assert!(contract.nft_token("3".to_string()).is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "Owner or admin's method")]
+    fn stranger_cannot_unlock_token_types() {
+        let mut contract = setup_contract(true);
+
+        testing_env!(get_context(accounts(1)).build());
+        contract.unlock_token_types(vec!["A".to_string()]);
+    }
+
+    #[test]
+    fn admin_locks_and_unlocks_token_types() {
+        let mut contract = setup_contract(false);
+        testing_env!(get_context(accounts(0)).build());
+        contract.grant_admin(accounts(2));
+
+        testing_env!(get_context(accounts(2)).build());
+        contract.lock_token_types(vec!["A".to_string()]);
+        assert_eq!(contract.get_token_types_locked(), vec!["A".to_string()]);
+        contract.unlock_token_types(vec!["A".to_string()]);
+        assert!(contract.get_token_types_locked().is_empty());
+
+        testing_env!(get_context(accounts(0)).build());
+        contract.revoke_admin(accounts(2));
+        assert!(!contract.is_admin(accounts(2)));
+    }
+
+    fn ms(ms: u64) -> u64 {
+        ms * 1_000_000
+    }
+
+    #[test]
+    fn scheduled_unlock_opens_transfers() {
+        let mut contract = setup_contract(true);
+        let token_id = mint(&mut contract, accounts(1), Some("A".to_string()));
+
+        testing_env!(get_context(accounts(0)).build());
+        contract.schedule_unlock("A".to_string(), U64(1_000));
+        assert!(contract.is_token_locked(token_id.clone()));
+        assert_eq!(contract.get_scheduled_unlocks().len(), 1);
+
+        testing_env!(get_context(accounts(1))
+            .block_timestamp(ms(1_000))
+            .attached_deposit(1)
+            .build());
+        assert!(!contract.is_token_locked(token_id.clone()));
+        assert!(contract.get_scheduled_unlocks().is_empty());
+        assert!(contract.get_token_types_locked().is_empty());
+        contract.nft_transfer(accounts(2), token_id.clone(), 0, None);
+        assert_eq!(contract.nft_token(token_id).unwrap().owner_id, accounts(2).to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Token transfers are locked")]
+    fn transfer_locked_before_scheduled_unlock() {
+        let mut contract = setup_contract(true);
+        let token_id = mint(&mut contract, accounts(1), Some("A".to_string()));
+
+        testing_env!(get_context(accounts(0)).build());
+        contract.schedule_unlock("A".to_string(), U64(1_000));
+
+        testing_env!(get_context(accounts(1))
+            .block_timestamp(ms(999))
+            .attached_deposit(1)
+            .build());
+        contract.nft_transfer(accounts(2), token_id, 0, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not allowed to mint this token type")]
+    fn stranger_cannot_mint_typed_token() {
+        let mut contract = setup_contract(false);
+
+        testing_env!(get_context(accounts(1))
+            .attached_deposit(MINT_STORAGE_COST)
+            .build());
+        contract.nft_mint(None, sample_token_metadata(), None, None, Some("A".to_string()));
+    }
+
+    #[test]
+    fn type_minter_mints_only_granted_type() {
+        let mut contract = setup_contract(false);
+        testing_env!(get_context(accounts(0)).build());
+        let mut supply_cap_by_type = HashMap::new();
+        supply_cap_by_type.insert("B".to_string(), U64(10));
+        contract.add_token_types(supply_cap_by_type, None);
+        contract.grant_minter(accounts(1), Some("A".to_string()));
+
+        assert_eq!(contract.get_minters_for_type("A".to_string()), vec![accounts(1).to_string()]);
+        assert!(contract.can_mint(accounts(1), Some("A".to_string())));
+        assert!(!contract.can_mint(accounts(1), Some("B".to_string())));
+        assert!(!contract.can_mint(accounts(1), None));
+
+        testing_env!(get_context(accounts(1))
+            .attached_deposit(MINT_STORAGE_COST)
+            .build());
+        contract.nft_mint(None, sample_token_metadata(), None, None, Some("A".to_string()));
+        assert_eq!(contract.nft_supply_for_type(&"A".to_string()).0, 1);
+
+        testing_env!(get_context(accounts(0)).build());
+        contract.revoke_minter(accounts(1), Some("A".to_string()));
+        assert!(!contract.can_mint(accounts(1), Some("A".to_string())));
+        assert!(contract.get_minters_for_type("A".to_string()).is_empty());
+    }
+
+    #[test]
+    fn untyped_mints_open_to_everyone() {
+        let mut contract = setup_contract(false);
+        assert!(!contract.can_mint(accounts(1), None));
+        testing_env!(get_context(accounts(0)).build());
+        contract.set_untyped_mints_open(true);
+
+        testing_env!(get_context(accounts(1))
+            .attached_deposit(MINT_STORAGE_COST)
+            .build());
+        contract.nft_mint(None, sample_token_metadata(), None, None, None);
+        assert_eq!(contract.nft_supply_for_owner(accounts(1).into()).0, 1);
+        assert!(!contract.can_mint(accounts(1), Some("A".to_string())));
+    }
+
+    const NEAR_PRICE: Balance = 1_000_000_000_000_000_000_000_000;
+
+    fn sample_mint_config() -> MintConfig {
+        MintConfig {
+            metadata: sample_token_metadata(),
+            price: Some(U128(NEAR_PRICE)),
+            ft_prices: HashMap::new(),
+            starts_at: None,
+            ends_at: None,
+            limit_per_account: None,
+            max_per_tx: None,
+        }
+    }
+
+    #[test]
+    fn paid_mint_with_near() {
+        let mut contract = setup_contract(false);
+        testing_env!(get_context(accounts(0)).build());
+        contract.set_mint_config("A".to_string(), Some(sample_mint_config()));
+
+        testing_env!(get_context(accounts(1))
+            .attached_deposit(2 * NEAR_PRICE + 2 * MINT_STORAGE_COST)
+            .build());
+        let token_ids = contract.nft_mint_paid("A".to_string(), Some(2));
+
+        assert_eq!(token_ids.len(), 2);
+        assert_eq!(contract.nft_supply_for_type(&"A".to_string()).0, 2);
+        assert_eq!(contract.nft_supply_for_owner(accounts(1).into()).0, 2);
+        assert_eq!(contract.get_paid_mints_for_account("A".to_string(), accounts(1)), 2);
+        assert_eq!(contract.get_mint_proceeds("near".to_string()).0, 2 * NEAR_PRICE);
+    }
+
+    #[test]
+    #[should_panic(expected = "Must attach")]
+    fn paid_mint_requires_price() {
+        let mut contract = setup_contract(false);
+        testing_env!(get_context(accounts(0)).build());
+        contract.set_mint_config("A".to_string(), Some(sample_mint_config()));
+
+        testing_env!(get_context(accounts(1))
+            .attached_deposit(MINT_STORAGE_COST)
+            .build());
+        contract.nft_mint_paid("A".to_string(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Mint has not started")]
+    fn paid_mint_before_start() {
+        let mut contract = setup_contract(false);
+        testing_env!(get_context(accounts(0)).build());
+        let mut config = sample_mint_config();
+        config.starts_at = Some(U64(1_000));
+        contract.set_mint_config("A".to_string(), Some(config));
+
+        testing_env!(get_context(accounts(1))
+            .block_timestamp(ms(999))
+            .attached_deposit(NEAR_PRICE + MINT_STORAGE_COST)
+            .build());
+        contract.nft_mint_paid("A".to_string(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "FT price must be greater than 0")]
+    fn mint_config_rejects_free_ft_mints() {
+        let mut contract = setup_contract(false);
+        testing_env!(get_context(accounts(0)).build());
+        let mut config = sample_mint_config();
+        config.ft_prices.insert(accounts(3).into(), U128(0));
+        contract.set_mint_config("A".to_string(), Some(config));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot mint more than 1 per account")]
+    fn paid_mint_limit_per_account() {
+        let mut contract = setup_contract(false);
+        testing_env!(get_context(accounts(0)).build());
+        let mut config = sample_mint_config();
+        config.limit_per_account = Some(1);
+        contract.set_mint_config("A".to_string(), Some(config));
+
+        testing_env!(get_context(accounts(1))
+            .attached_deposit(NEAR_PRICE + MINT_STORAGE_COST)
+            .build());
+        contract.nft_mint_paid("A".to_string(), None);
+        contract.nft_mint_paid("A".to_string(), None);
+    }
+
+    #[test]
+    fn paid_mint_with_ft_returns_excess() {
+        let mut contract = setup_contract(false);
+        testing_env!(get_context(accounts(0)).build());
+        let mut config = sample_mint_config();
+        config.price = None;
+        config.ft_prices.insert(accounts(3).to_string(), U128(100));
+        contract.set_mint_config("A".to_string(), Some(config));
+
+        testing_env!(get_context(accounts(3)).build());
+        let unused = contract.ft_on_transfer(
+            accounts(1).to_string(),
+            U128(250),
+            r#"{"token_type":"A","quantity":2}"#.to_string(),
+        );
+
+        match unused {
+            PromiseOrValue::Value(unused) => assert_eq!(unused.0, 50),
+            PromiseOrValue::Promise(_) => panic!("Expected a value"),
+        }
+        assert_eq!(contract.nft_supply_for_owner(accounts(1).into()).0, 2);
+        assert_eq!(contract.get_mint_proceeds(accounts(3).to_string()).0, 200);
+    }
+
+    fn sample_series_metadata() -> TokenMetadata {
+        TokenMetadata {
+            title: Some("Series".to_string()),
+            description: Some("Shared by every edition".to_string()),
+            media: Some("https://example.com/series-media.png".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn series_editions_merge_template() {
+        let mut contract = setup_contract(false);
+        testing_env!(get_context(accounts(0)).attached_deposit(MINT_STORAGE_COST).build());
+        let series_id = contract.create_series(sample_series_metadata(), U64(2), None, None, None);
+
+        testing_env!(get_context(accounts(0)).attached_deposit(MINT_STORAGE_COST).build());
+        let first = contract.nft_mint_series(series_id, None, Some(accounts(1)));
+        let overrides = TokenMetadata {
+            title: Some("Series #2".to_string()),
+            ..Default::default()
+        };
+        let second = contract.nft_mint_series(series_id, Some(overrides), Some(accounts(1)));
+        assert_eq!(first, "1:1");
+        assert_eq!(second, "1:2");
+
+        let metadata = contract.nft_token(second).unwrap().metadata;
+        assert_eq!(metadata.title, Some("Series #2".to_string()));
+        assert_eq!(metadata.media, Some("https://example.com/series-media.png".to_string()));
+        assert_eq!(metadata.copies, Some(2));
+        assert_eq!(contract.nft_token(first).unwrap().metadata.title, Some("Series".to_string()));
+        assert_eq!(contract.get_series(series_id).unwrap().series.minted, 2);
+    }
+
+    #[test]
+    fn series_edition_uses_less_storage() {
+        let mut contract = setup_contract(false);
+        testing_env!(get_context(accounts(0)).attached_deposit(MINT_STORAGE_COST).build());
+        let series_id = contract.create_series(sample_series_metadata(), U64(10), None, None, None);
+
+        testing_env!(get_context(accounts(0)).attached_deposit(MINT_STORAGE_COST).build());
+        let initial_storage_usage = env::storage_usage();
+        contract.nft_mint(None, sample_series_metadata(), None, Some(accounts(1)), None);
+        let full_token_bytes = env::storage_usage() - initial_storage_usage;
+
+        let initial_storage_usage = env::storage_usage();
+        contract.nft_mint_series(series_id, None, Some(accounts(2)));
+        let edition_bytes = env::storage_usage() - initial_storage_usage;
+
+        assert!(edition_bytes < full_token_bytes);
+    }
+
+    #[test]
+    #[should_panic(expected = "Series is sold out")]
+    fn series_mint_past_copies() {
+        let mut contract = setup_contract(false);
+        testing_env!(get_context(accounts(0)).attached_deposit(MINT_STORAGE_COST).build());
+        let series_id = contract.create_series(sample_series_metadata(), U64(1), None, None, None);
+
+        testing_env!(get_context(accounts(0)).attached_deposit(MINT_STORAGE_COST).build());
+        contract.nft_mint_series(series_id, None, None);
+        contract.nft_mint_series(series_id, None, None);
+    }
+
+    #[test]
+    fn series_with_price_open_to_everyone() {
+        let mut contract = setup_contract(false);
+        testing_env!(get_context(accounts(0)).attached_deposit(MINT_STORAGE_COST).build());
+        let series_id = contract.create_series(sample_series_metadata(), U64(5), Some(U128(NEAR_PRICE)), None, None);
+
+        testing_env!(get_context(accounts(1))
+            .attached_deposit(NEAR_PRICE + MINT_STORAGE_COST)
+            .build());
+        let token_id = contract.nft_mint_series(series_id, None, None);
+        assert_eq!(contract.nft_token(token_id).unwrap().owner_id, accounts(1).to_string());
+        assert_eq!(contract.get_mint_proceeds("near".to_string()).0, NEAR_PRICE);
+    }
+
+    #[test]
+    fn public_series_mint_ignores_metadata() {
+        let mut contract = setup_contract(false);
+        testing_env!(get_context(accounts(0)).attached_deposit(MINT_STORAGE_COST).build());
+        let series_id = contract.create_series(sample_series_metadata(), U64(5), Some(U128(NEAR_PRICE)), None, None);
+
+        testing_env!(get_context(accounts(1))
+            .attached_deposit(NEAR_PRICE + MINT_STORAGE_COST)
+            .build());
+        let overrides = TokenMetadata {
+            media: Some("https://example.com/other-media.png".to_string()),
+            ..Default::default()
+        };
+        let token_id = contract.nft_mint_series(series_id, Some(overrides), None);
+        let metadata = contract.nft_token(token_id).unwrap().metadata;
+        assert_eq!(metadata.media, Some("https://example.com/series-media.png".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Token ids like series_id:edition are reserved for series")]
+    fn nft_mint_rejects_series_token_id() {
+        let mut contract = setup_contract(false);
+        testing_env!(get_context(accounts(0)).attached_deposit(MINT_STORAGE_COST).build());
+        contract.nft_mint(Some("1:1".to_string()), sample_token_metadata(), None, None, None);
+    }
+
+    fn sample_mint_args(receiver_id: ValidAccountId, token_type: Option<TokenType>) -> MintArgs {
+        MintArgs {
+            token_id: None,
+            metadata: sample_token_metadata(),
+            perpetual_royalties: None,
+            receiver_id: Some(receiver_id),
+            token_type,
+        }
+    }
+
+    #[test]
+    fn batch_mint_within_estimate() {
+        let mut contract = setup_contract(false);
+        let mints = vec![
+            sample_mint_args(accounts(1), Some("A".to_string())),
+            sample_mint_args(accounts(1), Some("A".to_string())),
+            sample_mint_args(accounts(2), None),
+        ];
+        testing_env!(get_context(accounts(0)).build());
+        let estimate = contract.nft_mint_cost_estimate(vec![
+            sample_mint_args(accounts(1), Some("A".to_string())),
+            sample_mint_args(accounts(1), Some("A".to_string())),
+            sample_mint_args(accounts(2), None),
+        ]);
+
+        testing_env!(get_context(accounts(0)).attached_deposit(estimate.0).build());
+        let initial_storage_usage = env::storage_usage();
+        let token_ids = contract.nft_batch_mint(mints);
+        let used_bytes = env::storage_usage() - initial_storage_usage;
+
+        assert_eq!(token_ids, vec!["1".to_string(), "2".to_string(), "3".to_string()]);
+        assert_eq!(contract.nft_supply_for_type(&"A".to_string()).0, 2);
+        assert_eq!(contract.nft_supply_for_owner(accounts(2).into()).0, 1);
+        assert!(env::storage_byte_cost() * Balance::from(used_bytes) <= estimate.0);
+    }
+
+    #[test]
+    fn mint_cost_estimate_matches_used_storage() {
+        let mut contract = setup_contract(false);
+        for _ in 0..9 {
+            mint(&mut contract, accounts(1), None);
+        }
+        testing_env!(get_context(accounts(1)).attached_deposit(1).build());
+        contract.nft_burn("9".to_string());
+
+        // existing owner, new owner and new token_type, existing token_type and a token_id passed in
+        let mints = || {
+            vec![
+                sample_mint_args(accounts(1), None),
+                sample_mint_args(accounts(2), Some("A".to_string())),
+                sample_mint_args(accounts(2), Some("A".to_string())),
+                MintArgs { token_id: Some("explicit".to_string()), ..sample_mint_args(accounts(3), None) },
+            ]
+        };
+        testing_env!(get_context(accounts(0)).build());
+        let estimate = contract.nft_mint_cost_estimate(mints());
+
+        testing_env!(get_context(accounts(0)).attached_deposit(estimate.0).build());
+        let initial_storage_usage = env::storage_usage();
+        let token_ids = contract.nft_batch_mint(mints());
+        let used_bytes = env::storage_usage() - initial_storage_usage + 4 * contract.extra_storage_in_bytes_per_token;
+
+        // the burned "9" isn't reissued, "10" is one byte longer
+        assert_eq!(token_ids[0], "10");
+        assert_eq!(estimate.0, env::storage_byte_cost() * Balance::from(used_bytes));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot mint 11 more of token type A")]
+    fn batch_mint_past_supply_cap() {
+        let mut contract = setup_contract(false);
+        let mints = (0..11).map(|_| sample_mint_args(accounts(1), Some("A".to_string()))).collect();
+
+        testing_env!(get_context(accounts(0)).attached_deposit(11 * MINT_STORAGE_COST).build());
+        contract.nft_batch_mint(mints);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token a already exists")]
+    fn batch_mint_duplicate_token_id() {
+        let mut contract = setup_contract(false);
+        let mut first = sample_mint_args(accounts(1), None);
+        first.token_id = Some("a".to_string());
+        let mut second = sample_mint_args(accounts(1), None);
+        second.token_id = Some("a".to_string());
+
+        testing_env!(get_context(accounts(0)).attached_deposit(2 * MINT_STORAGE_COST).build());
+        contract.nft_batch_mint(vec![first, second]);
     }
 }
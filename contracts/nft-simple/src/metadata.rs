@@ -12,7 +12,7 @@ pub struct NFTMetadata {
     pub reference_hash: Option<Base64VecU8>, // Base64-encoded sha256 hash of JSON from reference field. Required if `reference` is included.
 }
 
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Default)]
 #[serde(crate = "near_sdk::serde")]
 pub struct TokenMetadata {
     pub title: Option<String>, // ex. "Arch Nemesis: Mail Carrier" or "Parcel #5055"
@@ -29,6 +29,26 @@ pub struct TokenMetadata {
     pub reference_hash: Option<Base64VecU8>, // Base64-encoded sha256 hash of JSON from reference field. Required if `reference` is included.
 }
 
+impl TokenMetadata {
+    /// CUSTOM - series template with the fields an edition overrides
+    pub(crate) fn merged_with(self, overrides: TokenMetadata) -> TokenMetadata {
+        TokenMetadata {
+            title: overrides.title.or(self.title),
+            description: overrides.description.or(self.description),
+            media: overrides.media.or(self.media),
+            media_hash: overrides.media_hash.or(self.media_hash),
+            copies: overrides.copies.or(self.copies),
+            issued_at: overrides.issued_at.or(self.issued_at),
+            expires_at: overrides.expires_at.or(self.expires_at),
+            starts_at: overrides.starts_at.or(self.starts_at),
+            updated_at: overrides.updated_at.or(self.updated_at),
+            extra: overrides.extra.or(self.extra),
+            reference: overrides.reference.or(self.reference),
+            reference_hash: overrides.reference_hash.or(self.reference_hash),
+        }
+    }
+}
+
 pub trait NonFungibleTokenMetadata {
     fn nft_metadata(&self) -> NFTMetadata;
 }
@@ -0,0 +1,301 @@
+use crate::*;
+
+/// Version of the current `Contract` layout.
+/// Bump it whenever a field is added or changed, and keep the previous layout in `VersionedContract`
+/// so `migrate` can upgrade already deployed state.
+pub const CONTRACT_VERSION: u32 = 8;
+/// State written before versioning has no version key and is version 1
+const STATE_VERSION_KEY: &[u8] = b"VERSION";
+
+/// Contract layout up to changelog 1.0.10, before state versioning, version 2 kept it unchanged
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractV1 {
+    pub tokens_per_owner: LookupMap<AccountId, UnorderedSet<TokenId>>,
+    pub tokens_by_id: LookupMap<TokenId, Token>,
+    pub token_metadata_by_id: UnorderedMap<TokenId, TokenMetadata>,
+    pub owner_id: AccountId,
+    pub extra_storage_in_bytes_per_token: StorageUsage,
+    pub metadata: LazyOption<NFTMetadata>,
+    pub supply_cap_by_type: TypeSupplyCaps,
+    pub tokens_per_type: LookupMap<TokenType, UnorderedSet<TokenId>>,
+    pub token_types_locked: UnorderedSet<TokenType>,
+    pub contract_royalty: u32,
+}
+
+/// Contract layout of version 3, before minting roles
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractV3 {
+    pub tokens_per_owner: LookupMap<AccountId, UnorderedSet<TokenId>>,
+    pub tokens_by_id: LookupMap<TokenId, Token>,
+    pub token_metadata_by_id: UnorderedMap<TokenId, TokenMetadata>,
+    pub owner_id: AccountId,
+    pub extra_storage_in_bytes_per_token: StorageUsage,
+    pub metadata: LazyOption<NFTMetadata>,
+    pub supply_cap_by_type: TypeSupplyCaps,
+    pub tokens_per_type: LookupMap<TokenType, UnorderedSet<TokenId>>,
+    pub token_types_locked: UnorderedSet<TokenType>,
+    pub contract_royalty: u32,
+    pub admins: UnorderedSet<AccountId>,
+    pub scheduled_unlocks: UnorderedMap<TokenType, u64>,
+}
+
+/// Contract layout of version 4, before paid mints
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractV4 {
+    pub tokens_per_owner: LookupMap<AccountId, UnorderedSet<TokenId>>,
+    pub tokens_by_id: LookupMap<TokenId, Token>,
+    pub token_metadata_by_id: UnorderedMap<TokenId, TokenMetadata>,
+    pub owner_id: AccountId,
+    pub extra_storage_in_bytes_per_token: StorageUsage,
+    pub metadata: LazyOption<NFTMetadata>,
+    pub supply_cap_by_type: TypeSupplyCaps,
+    pub tokens_per_type: LookupMap<TokenType, UnorderedSet<TokenId>>,
+    pub token_types_locked: UnorderedSet<TokenType>,
+    pub contract_royalty: u32,
+    pub admins: UnorderedSet<AccountId>,
+    pub scheduled_unlocks: UnorderedMap<TokenType, u64>,
+    pub minters: UnorderedSet<AccountId>,
+    pub minters_per_type: LookupMap<TokenType, UnorderedSet<AccountId>>,
+    pub untyped_mints_open: bool,
+}
+
+/// Contract layout of version 5, before series
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractV5 {
+    pub tokens_per_owner: LookupMap<AccountId, UnorderedSet<TokenId>>,
+    pub tokens_by_id: LookupMap<TokenId, Token>,
+    pub token_metadata_by_id: UnorderedMap<TokenId, TokenMetadata>,
+    pub owner_id: AccountId,
+    pub extra_storage_in_bytes_per_token: StorageUsage,
+    pub metadata: LazyOption<NFTMetadata>,
+    pub supply_cap_by_type: TypeSupplyCaps,
+    pub tokens_per_type: LookupMap<TokenType, UnorderedSet<TokenId>>,
+    pub token_types_locked: UnorderedSet<TokenType>,
+    pub contract_royalty: u32,
+    pub admins: UnorderedSet<AccountId>,
+    pub scheduled_unlocks: UnorderedMap<TokenType, u64>,
+    pub minters: UnorderedSet<AccountId>,
+    pub minters_per_type: LookupMap<TokenType, UnorderedSet<AccountId>>,
+    pub untyped_mints_open: bool,
+    pub mint_configs: LookupMap<TokenType, MintConfig>,
+    pub paid_mints_per_account: LookupMap<String, u32>,
+    pub mint_proceeds: LookupMap<AccountId, Balance>,
+}
+
+/// Contract layout of version 6, before the auto token id counter
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractV6 {
+    pub tokens_per_owner: LookupMap<AccountId, UnorderedSet<TokenId>>,
+    pub tokens_by_id: LookupMap<TokenId, Token>,
+    pub token_metadata_by_id: UnorderedMap<TokenId, TokenMetadata>,
+    pub owner_id: AccountId,
+    pub extra_storage_in_bytes_per_token: StorageUsage,
+    pub metadata: LazyOption<NFTMetadata>,
+    pub supply_cap_by_type: TypeSupplyCaps,
+    pub tokens_per_type: LookupMap<TokenType, UnorderedSet<TokenId>>,
+    pub token_types_locked: UnorderedSet<TokenType>,
+    pub contract_royalty: u32,
+    pub admins: UnorderedSet<AccountId>,
+    pub scheduled_unlocks: UnorderedMap<TokenType, u64>,
+    pub minters: UnorderedSet<AccountId>,
+    pub minters_per_type: LookupMap<TokenType, UnorderedSet<AccountId>>,
+    pub untyped_mints_open: bool,
+    pub mint_configs: LookupMap<TokenType, MintConfig>,
+    pub paid_mints_per_account: LookupMap<String, u32>,
+    pub mint_proceeds: LookupMap<AccountId, Balance>,
+    pub series: UnorderedMap<SeriesId, Series>,
+    pub next_series_id: SeriesId,
+}
+
+pub enum VersionedContract {
+    V1(ContractV1),
+    V3(ContractV3),
+    V4(ContractV4),
+    V5(ContractV5),
+    V6(ContractV6),
+    /// same layout as the current one, unlock and mint times were in ns
+    V7(Contract),
+    Current(Contract),
+}
+
+impl VersionedContract {
+    /// reads the contract state using the layout of the stored version
+    pub(crate) fn read() -> Self {
+        match stored_contract_version() {
+            1 | 2 => VersionedContract::V1(env::state_read().expect("No contract state")),
+            3 => VersionedContract::V3(env::state_read().expect("No contract state")),
+            4 => VersionedContract::V4(env::state_read().expect("No contract state")),
+            5 => VersionedContract::V5(env::state_read().expect("No contract state")),
+            6 => VersionedContract::V6(env::state_read().expect("No contract state")),
+            7 => VersionedContract::V7(env::state_read().expect("No contract state")),
+            CONTRACT_VERSION => VersionedContract::Current(env::state_read().expect("No contract state")),
+            version => env::panic(format!("Unknown contract version {}", version).as_bytes()),
+        }
+    }
+
+    pub(crate) fn owner_id(&self) -> &AccountId {
+        match self {
+            VersionedContract::V1(contract) => &contract.owner_id,
+            VersionedContract::V3(contract) => &contract.owner_id,
+            VersionedContract::V4(contract) => &contract.owner_id,
+            VersionedContract::V5(contract) => &contract.owner_id,
+            VersionedContract::V6(contract) => &contract.owner_id,
+            VersionedContract::V7(contract) => &contract.owner_id,
+            VersionedContract::Current(contract) => &contract.owner_id,
+        }
+    }
+
+    /// upgrades one layout at a time until the current one
+    pub(crate) fn into_current(self) -> Contract {
+        match self {
+            VersionedContract::V1(contract) => VersionedContract::V3(ContractV3 {
+                tokens_per_owner: contract.tokens_per_owner,
+                tokens_by_id: contract.tokens_by_id,
+                token_metadata_by_id: contract.token_metadata_by_id,
+                owner_id: contract.owner_id,
+                extra_storage_in_bytes_per_token: contract.extra_storage_in_bytes_per_token,
+                metadata: contract.metadata,
+                supply_cap_by_type: contract.supply_cap_by_type,
+                tokens_per_type: contract.tokens_per_type,
+                token_types_locked: contract.token_types_locked,
+                contract_royalty: contract.contract_royalty,
+                admins: UnorderedSet::new(StorageKey::Admins.try_to_vec().unwrap()),
+                scheduled_unlocks: UnorderedMap::new(StorageKey::ScheduledUnlocks.try_to_vec().unwrap()),
+            })
+            .into_current(),
+            VersionedContract::V3(contract) => VersionedContract::V4(ContractV4 {
+                tokens_per_owner: contract.tokens_per_owner,
+                tokens_by_id: contract.tokens_by_id,
+                token_metadata_by_id: contract.token_metadata_by_id,
+                owner_id: contract.owner_id,
+                extra_storage_in_bytes_per_token: contract.extra_storage_in_bytes_per_token,
+                metadata: contract.metadata,
+                supply_cap_by_type: contract.supply_cap_by_type,
+                tokens_per_type: contract.tokens_per_type,
+                token_types_locked: contract.token_types_locked,
+                contract_royalty: contract.contract_royalty,
+                admins: contract.admins,
+                scheduled_unlocks: contract.scheduled_unlocks,
+                minters: UnorderedSet::new(StorageKey::Minters.try_to_vec().unwrap()),
+                minters_per_type: LookupMap::new(StorageKey::MintersPerType.try_to_vec().unwrap()),
+                // anyone could mint before roles, keep untyped mints open
+                untyped_mints_open: true,
+            })
+            .into_current(),
+            VersionedContract::V4(contract) => VersionedContract::V5(ContractV5 {
+                tokens_per_owner: contract.tokens_per_owner,
+                tokens_by_id: contract.tokens_by_id,
+                token_metadata_by_id: contract.token_metadata_by_id,
+                owner_id: contract.owner_id,
+                extra_storage_in_bytes_per_token: contract.extra_storage_in_bytes_per_token,
+                metadata: contract.metadata,
+                supply_cap_by_type: contract.supply_cap_by_type,
+                tokens_per_type: contract.tokens_per_type,
+                token_types_locked: contract.token_types_locked,
+                contract_royalty: contract.contract_royalty,
+                admins: contract.admins,
+                scheduled_unlocks: contract.scheduled_unlocks,
+                minters: contract.minters,
+                minters_per_type: contract.minters_per_type,
+                untyped_mints_open: contract.untyped_mints_open,
+                mint_configs: LookupMap::new(StorageKey::MintConfigs.try_to_vec().unwrap()),
+                paid_mints_per_account: LookupMap::new(StorageKey::PaidMintsPerAccount.try_to_vec().unwrap()),
+                mint_proceeds: LookupMap::new(StorageKey::MintProceeds.try_to_vec().unwrap()),
+            })
+            .into_current(),
+            VersionedContract::V5(contract) => VersionedContract::V6(ContractV6 {
+                tokens_per_owner: contract.tokens_per_owner,
+                tokens_by_id: contract.tokens_by_id,
+                token_metadata_by_id: contract.token_metadata_by_id,
+                owner_id: contract.owner_id,
+                extra_storage_in_bytes_per_token: contract.extra_storage_in_bytes_per_token,
+                metadata: contract.metadata,
+                supply_cap_by_type: contract.supply_cap_by_type,
+                tokens_per_type: contract.tokens_per_type,
+                token_types_locked: contract.token_types_locked,
+                contract_royalty: contract.contract_royalty,
+                admins: contract.admins,
+                scheduled_unlocks: contract.scheduled_unlocks,
+                minters: contract.minters,
+                minters_per_type: contract.minters_per_type,
+                untyped_mints_open: contract.untyped_mints_open,
+                mint_configs: contract.mint_configs,
+                paid_mints_per_account: contract.paid_mints_per_account,
+                mint_proceeds: contract.mint_proceeds,
+                series: UnorderedMap::new(StorageKey::Series.try_to_vec().unwrap()),
+                next_series_id: 1,
+            })
+            .into_current(),
+            VersionedContract::V6(contract) => VersionedContract::V7(Contract {
+                // burned ids before the counter can't be known, ids still taken are skipped when minting
+                next_token_id: contract.token_metadata_by_id.len() + 1,
+                tokens_per_owner: contract.tokens_per_owner,
+                tokens_by_id: contract.tokens_by_id,
+                token_metadata_by_id: contract.token_metadata_by_id,
+                owner_id: contract.owner_id,
+                extra_storage_in_bytes_per_token: contract.extra_storage_in_bytes_per_token,
+                metadata: contract.metadata,
+                supply_cap_by_type: contract.supply_cap_by_type,
+                tokens_per_type: contract.tokens_per_type,
+                token_types_locked: contract.token_types_locked,
+                contract_royalty: contract.contract_royalty,
+                admins: contract.admins,
+                scheduled_unlocks: contract.scheduled_unlocks,
+                minters: contract.minters,
+                minters_per_type: contract.minters_per_type,
+                untyped_mints_open: contract.untyped_mints_open,
+                mint_configs: contract.mint_configs,
+                paid_mints_per_account: contract.paid_mints_per_account,
+                mint_proceeds: contract.mint_proceeds,
+                series: contract.series,
+                next_series_id: contract.next_series_id,
+            })
+            .into_current(),
+            VersionedContract::V7(mut contract) => {
+                for (token_type, unlock_at) in contract.scheduled_unlocks.to_vec() {
+                    contract.scheduled_unlocks.insert(&token_type, &(unlock_at / 1_000_000));
+                }
+                for token_type in contract.supply_cap_by_type.keys() {
+                    if let Some(mut mint_config) = contract.mint_configs.get(token_type) {
+                        mint_config.starts_at = mint_config.starts_at.map(|starts_at| U64(starts_at.0 / 1_000_000));
+                        mint_config.ends_at = mint_config.ends_at.map(|ends_at| U64(ends_at.0 / 1_000_000));
+                        contract.mint_configs.insert(token_type, &mint_config);
+                    }
+                }
+                contract
+            }
+            VersionedContract::Current(contract) => contract,
+        }
+    }
+}
+
+pub(crate) fn stored_contract_version() -> u32 {
+    env::storage_read(STATE_VERSION_KEY)
+        .map(|version| u32::try_from_slice(&version).expect("Invalid contract version"))
+        .unwrap_or(1)
+}
+
+pub(crate) fn write_contract_version() {
+    env::storage_write(STATE_VERSION_KEY, &CONTRACT_VERSION.try_to_vec().unwrap());
+}
+
+#[near_bindgen]
+impl Contract {
+    /// deploy the new code and call migrate (owner or contract account) to upgrade the state in place
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let state = VersionedContract::read();
+        let predecessor_account_id = env::predecessor_account_id();
+        assert!(
+            &predecessor_account_id == state.owner_id() || predecessor_account_id == env::current_account_id(),
+            "Owner's method"
+        );
+        let this = state.into_current();
+        write_contract_version();
+        this
+    }
+
+    pub fn contract_version(&self) -> u32 {
+        stored_contract_version()
+    }
+}
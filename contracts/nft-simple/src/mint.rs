@@ -1,4 +1,20 @@
 use crate::*;
+use std::collections::HashSet;
+use std::mem::size_of;
+
+/// NEAR charges 40 bytes for every record on top of its key and value
+const STORAGE_BYTES_PER_RECORD: u64 = 40;
+
+/// CUSTOM - nft_mint args for nft_batch_mint and nft_mint_cost_estimate
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MintArgs {
+    pub token_id: Option<TokenId>,
+    pub metadata: TokenMetadata,
+    pub perpetual_royalties: Option<HashMap<AccountId, u32>>,
+    pub receiver_id: Option<ValidAccountId>,
+    pub token_type: Option<TokenType>,
+}
 
 #[near_bindgen]
 impl Contract {
@@ -11,10 +27,13 @@ impl Contract {
         receiver_id: Option<ValidAccountId>,
         token_type: Option<TokenType>,
     ) {
-
-        let mut final_token_id = format!("{}", self.token_metadata_by_id.len() + 1);
-        if let Some(token_id) = token_id {
-            final_token_id = token_id
+        // CUSTOM - owner, admins and minters of the token_type, untyped mints can be open to everyone
+        assert!(
+            self.internal_can_mint(&env::predecessor_account_id(), token_type.as_ref()),
+            "Not allowed to mint this token type"
+        );
+        if let Some(token_id) = &token_id {
+            assert!(series_id_from_token_id(token_id).is_none(), "Token ids like series_id:edition are reserved for series");
         }
 
         let initial_storage_usage = env::storage_usage();
@@ -24,20 +43,155 @@ impl Contract {
         }
 
         // CUSTOM - create royalty map
-        let mut royalty = HashMap::new();
-        let mut total_perpetual = 0;
-        // user added perpetual_royalties (percentage paid with every transfer)
-        if let Some(perpetual_royalties) = perpetual_royalties {
-            assert!(perpetual_royalties.len() < 7, "Cannot add more than 6 perpetual royalty amounts");
-            for (account, amount) in perpetual_royalties {
-                royalty.insert(account, amount);
-                total_perpetual += amount;
+        let royalty = royalty_from_perpetual(perpetual_royalties);
+
+        self.internal_mint(token_id, metadata, royalty, owner_id, token_type);
+
+        let new_token_size_in_bytes = env::storage_usage() - initial_storage_usage;
+        let required_storage_in_bytes =
+            self.extra_storage_in_bytes_per_token + new_token_size_in_bytes;
+
+        refund_deposit(required_storage_in_bytes);
+    }
+
+    /// CUSTOM - mints all or nothing, the whole batch is validated first and the deposit refunded once
+    #[payable]
+    pub fn nft_batch_mint(&mut self, mints: Vec<MintArgs>) -> Vec<TokenId> {
+        assert!(!mints.is_empty(), "Nothing to mint");
+        let predecessor_account_id = env::predecessor_account_id();
+
+        let mut token_ids = HashSet::new();
+        let mut mints_per_type: HashMap<TokenType, u64> = HashMap::new();
+        let mut royalties = Vec::with_capacity(mints.len());
+        for args in &mints {
+            assert!(
+                self.internal_can_mint(&predecessor_account_id, args.token_type.as_ref()),
+                "Not allowed to mint this token type"
+            );
+            if let Some(token_id) = &args.token_id {
+                assert!(series_id_from_token_id(token_id).is_none(), "Token ids like series_id:edition are reserved for series");
+                assert!(
+                    token_ids.insert(token_id.clone()) && self.tokens_by_id.get(token_id).is_none(),
+                    "Token {} already exists",
+                    token_id
+                );
+            }
+            if let Some(token_type) = &args.token_type {
+                *mints_per_type.entry(token_type.clone()).or_insert(0) += 1;
+            }
+            royalties.push(royalty_from_perpetual(args.perpetual_royalties.clone()));
+        }
+        for (token_type, count) in &mints_per_type {
+            let cap = u64::from(*self.supply_cap_by_type.get(token_type).expect("Token type must have supply cap."));
+            let supply = u64::from(self.nft_supply_for_type(token_type));
+            assert!(supply + count <= cap, "Cannot mint {} more of token type {}", count, token_type);
+        }
+
+        let initial_storage_usage = env::storage_usage();
+        let count = mints.len() as u64;
+        let minted = mints
+            .into_iter()
+            .zip(royalties)
+            .map(|(args, royalty)| {
+                let owner_id = args
+                    .receiver_id
+                    .map(|a| a.into())
+                    .unwrap_or_else(|| predecessor_account_id.clone());
+                self.internal_mint(args.token_id, args.metadata, royalty, owner_id, args.token_type)
+            })
+            .collect();
+
+        let required_storage_in_bytes =
+            self.extra_storage_in_bytes_per_token * count + env::storage_usage() - initial_storage_usage;
+        refund_deposit(required_storage_in_bytes);
+        minted
+    }
+
+    /// CUSTOM - deposit to attach to nft_mint or nft_batch_mint, anything not used is refunded
+    pub fn nft_mint_cost_estimate(&self, mints: Vec<MintArgs>) -> U128 {
+        // auto ids as internal_mint hands them out, skipping ids taken before or earlier in the batch
+        let mut next_token_id = self.next_token_id;
+        let mut batch_ids = HashSet::new();
+        let mut new_types = HashSet::new();
+        let mut new_owners = HashSet::new();
+        let mut bytes = 0;
+        for args in &mints {
+            let token_id = args.token_id.clone().unwrap_or_else(|| loop {
+                let token_id = next_token_id.to_string();
+                next_token_id += 1;
+                if self.tokens_by_id.get(&token_id).is_none() && !batch_ids.contains(&token_id) {
+                    break token_id;
+                }
+            });
+            batch_ids.insert(token_id.clone());
+            let owner_id = args
+                .receiver_id
+                .as_ref()
+                .map(|a| a.to_string())
+                .unwrap_or_else(env::predecessor_account_id);
+            let token = Token {
+                owner_id,
+                approved_account_ids: Default::default(),
+                next_approval_id: 0,
+                royalty: args.perpetual_royalties.clone().unwrap_or_default(),
+                token_type: args.token_type.clone(),
+            };
+            bytes += self.extra_storage_in_bytes_per_token + bytes_for_token(&token_id, &token, &args.metadata);
+            if self.tokens_per_owner.get(&token.owner_id).is_none() && new_owners.insert(token.owner_id.clone()) {
+                bytes += bytes_for_owner_set(&token.owner_id);
+            }
+            if let Some(token_type) = &args.token_type {
+                if self.tokens_per_type.get(token_type).is_none() && new_types.insert(token_type) {
+                    bytes += bytes_for_token_set(token_type);
+                }
             }
         }
-        // royalty limit for minter capped at 20%
-        assert!(total_perpetual <= MINTER_ROYALTY_CAP, "Perpetual royalties cannot be more than 20%");
+        U128(env::storage_byte_cost() * Balance::from(bytes))
+    }
+}
 
-        // CUSTOM - enforce minting caps by token_type 
+/// records internal_mint writes for a token: tokens_by_id, token_metadata_by_id (index, key and value)
+/// and the owner's (and token_type's) set (index and element)
+fn bytes_for_token(token_id: &TokenId, token: &Token, metadata: &TokenMetadata) -> u64 {
+    let token_id_len = token_id.try_to_vec().unwrap().len() as u64;
+    let index_len = size_of::<u64>() as u64;
+    let token_bytes = 1 + token_id_len + token.try_to_vec().unwrap().len() as u64;
+    let metadata_bytes = (2 + token_id_len + index_len)
+        + (2 + index_len + token_id_len)
+        + (2 + index_len + metadata.try_to_vec().unwrap().len() as u64);
+    let inner_prefix_len = StorageKey::TokenPerOwnerInner { account_id_hash: [0; 32] }.try_to_vec().unwrap().len() as u64;
+    let set_bytes = (inner_prefix_len + 1 + token_id_len + index_len) + (inner_prefix_len + 1 + index_len + token_id_len);
+    let sets = if token.token_type.is_some() { 2 } else { 1 };
+    token_bytes + metadata_bytes + set_bytes * sets + STORAGE_BYTES_PER_RECORD * (4 + 2 * sets)
+}
+
+/// tokens_per_owner entry for an owner without tokens
+fn bytes_for_owner_set(owner_id: &AccountId) -> u64 {
+    let inner_prefix = StorageKey::TokenPerOwnerInner { account_id_hash: [0; 32] }.try_to_vec().unwrap();
+    let set: UnorderedSet<TokenId> = UnorderedSet::new(inner_prefix);
+    1 + owner_id.try_to_vec().unwrap().len() as u64 + set.try_to_vec().unwrap().len() as u64 + STORAGE_BYTES_PER_RECORD
+}
+
+/// tokens_per_type entry for a token_type without tokens
+fn bytes_for_token_set(token_type: &TokenType) -> u64 {
+    let inner_prefix = StorageKey::TokensPerTypeInner { token_type_hash: [0; 32] }.try_to_vec().unwrap();
+    let set: UnorderedSet<TokenId> = UnorderedSet::new(inner_prefix);
+    1 + token_type.try_to_vec().unwrap().len() as u64 + set.try_to_vec().unwrap().len() as u64 + STORAGE_BYTES_PER_RECORD
+}
+
+impl Contract {
+    /// adds the token and logs nft_mint, callers check permissions and charge for storage
+    pub(crate) fn internal_mint(
+        &mut self,
+        token_id: Option<TokenId>,
+        metadata: TokenMetadata,
+        royalty: HashMap<AccountId, u32>,
+        owner_id: AccountId,
+        token_type: Option<TokenType>,
+    ) -> TokenId {
+        let final_token_id = token_id.unwrap_or_else(|| self.internal_next_token_id());
+
+        // CUSTOM - enforce minting caps by token_type
         if token_type.is_some() {
             let token_type = token_type.clone().unwrap();
             let cap = u64::from(*self.supply_cap_by_type.get(&token_type).expect("Token type must have supply cap."));
@@ -74,10 +228,24 @@ impl Contract {
         self.token_metadata_by_id.insert(&final_token_id, &metadata);
         self.internal_add_token_to_owner(&token.owner_id, &final_token_id);
 
-        let new_token_size_in_bytes = env::storage_usage() - initial_storage_usage;
-        let required_storage_in_bytes =
-            self.extra_storage_in_bytes_per_token + new_token_size_in_bytes;
+        NftEventKind::NftMint(vec![NftMintLog {
+            owner_id: token.owner_id.clone(),
+            token_ids: vec![final_token_id.clone()],
+            memo: None,
+        }])
+        .emit();
 
-        refund_deposit(required_storage_in_bytes);
+        final_token_id
+    }
+
+    /// auto ids count up from next_token_id, skipping ids already taken by a token_id passed to nft_mint
+    fn internal_next_token_id(&mut self) -> TokenId {
+        loop {
+            let token_id = self.next_token_id.to_string();
+            self.next_token_id += 1;
+            if self.tokens_by_id.get(&token_id).is_none() {
+                return token_id;
+            }
+        }
     }
-}
\ No newline at end of file
+}
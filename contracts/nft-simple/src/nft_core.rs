@@ -1,11 +1,14 @@
 use crate::*;
 use near_sdk::json_types::{ValidAccountId};
-use near_sdk::{ext_contract, log, Gas, PromiseResult};
+use near_sdk::{ext_contract, Gas, PromiseResult};
 
 const GAS_FOR_NFT_APPROVE: Gas = 10_000_000_000_000;
+const GAS_FOR_NFT_ON_REVOKE: Gas = 10_000_000_000_000;
 const GAS_FOR_RESOLVE_TRANSFER: Gas = 10_000_000_000_000;
 const GAS_FOR_NFT_TRANSFER_CALL: Gas = 25_000_000_000_000 + GAS_FOR_RESOLVE_TRANSFER;
 const NO_DEPOSIT: Balance = 0;
+/// each notification takes GAS_FOR_NFT_ON_REVOKE, keeps nft_revoke_all within the gas limit
+const MAX_REVOKE_NOTIFICATIONS: usize = 10;
 
 pub trait NonFungibleTokenCore {
     fn nft_transfer(
@@ -47,9 +50,11 @@ pub trait NonFungibleTokenCore {
         approval_id: Option<u64>,
     ) -> bool;
 
-    fn nft_revoke(&mut self, token_id: TokenId, account_id: ValidAccountId);
+    /// CUSTOM - notify calls nft_on_revoke on the revoked account
+    fn nft_revoke(&mut self, token_id: TokenId, account_id: ValidAccountId, notify: Option<bool>);
 
-    fn nft_revoke_all(&mut self, token_id: TokenId);
+    /// CUSTOM - notify calls nft_on_revoke on every revoked account, up to MAX_REVOKE_NOTIFICATIONS
+    fn nft_revoke_all(&mut self, token_id: TokenId, notify: Option<bool>);
 
     fn nft_total_supply(&self) -> U128;
 
@@ -77,9 +82,27 @@ trait NonFungibleTokenApprovalsReceiver {
         approval_id: u64,
         msg: String,
     );
+
+    /// Called on revoked accounts when the owner passes notify, receivers don't have to implement it (revoke succeeds either way)
+    fn nft_on_revoke(
+        &mut self,
+        token_id: TokenId,
+        owner_id: AccountId,
+        approval_id: u64,
+    );
 }
 
-// TODO: create nft_on_revoke
+/// fire and forget, a receiver that fails or doesn't implement nft_on_revoke doesn't revert the revoke
+fn notify_revoke(token_id: TokenId, owner_id: AccountId, account_id: AccountId, approval_id: u64) {
+    ext_non_fungible_approval_receiver::nft_on_revoke(
+        token_id,
+        owner_id,
+        approval_id,
+        &account_id,
+        NO_DEPOSIT,
+        GAS_FOR_NFT_ON_REVOKE,
+    );
+}
 
 #[ext_contract(ext_self)]
 trait NonFungibleTokenResolver {
@@ -326,31 +349,46 @@ impl NonFungibleTokenCore for Contract {
     }
 
     #[payable]
-    fn nft_revoke(&mut self, token_id: TokenId, account_id: ValidAccountId) {
+    fn nft_revoke(&mut self, token_id: TokenId, account_id: ValidAccountId, notify: Option<bool>) {
         assert_one_yocto();
         let mut token = self.tokens_by_id.get(&token_id).expect("No token");
         let predecessor_account_id = env::predecessor_account_id();
         assert_eq!(&predecessor_account_id, &token.owner_id);
-        if token
+        if let Some(approval_id) = token
             .approved_account_ids
             .remove(account_id.as_ref())
-            .is_some()
         {
-            refund_approved_account_ids_iter(predecessor_account_id, [account_id.into()].iter());
+            refund_approved_account_ids_iter(predecessor_account_id.clone(), [account_id.clone().into()].iter());
             self.tokens_by_id.insert(&token_id, &token);
+            if notify.unwrap_or(false) {
+                notify_revoke(token_id, predecessor_account_id, account_id.into(), approval_id);
+            }
         }
     }
 
     #[payable]
-    fn nft_revoke_all(&mut self, token_id: TokenId) {
+    fn nft_revoke_all(&mut self, token_id: TokenId, notify: Option<bool>) {
         assert_one_yocto();
         let mut token = self.tokens_by_id.get(&token_id).expect("No token");
         let predecessor_account_id = env::predecessor_account_id();
         assert_eq!(&predecessor_account_id, &token.owner_id);
+        let notify = notify.unwrap_or(false);
+        if notify {
+            assert!(
+                token.approved_account_ids.len() <= MAX_REVOKE_NOTIFICATIONS,
+                "Cannot notify more than {} accounts, revoke them one by one or without notify",
+                MAX_REVOKE_NOTIFICATIONS
+            );
+        }
         if !token.approved_account_ids.is_empty() {
-            refund_approved_account_ids(predecessor_account_id, &token.approved_account_ids);
-            token.approved_account_ids.clear();
+            refund_approved_account_ids(predecessor_account_id.clone(), &token.approved_account_ids);
+            let approved_account_ids = std::mem::take(&mut token.approved_account_ids);
             self.tokens_by_id.insert(&token_id, &token);
+            if notify {
+                for (account_id, approval_id) in approved_account_ids {
+                    notify_revoke(token_id.clone(), predecessor_account_id.clone(), account_id, approval_id);
+                }
+            }
         }
     }
 
@@ -360,7 +398,7 @@ impl NonFungibleTokenCore for Contract {
 
     fn nft_token(&self, token_id: TokenId) -> Option<JsonToken> {
         if let Some(token) = self.tokens_by_id.get(&token_id) {
-            let metadata = self.token_metadata_by_id.get(&token_id).unwrap();
+            let metadata = self.internal_token_metadata(&token_id);
             Some(JsonToken {
                 token_id,
                 owner_id: token.owner_id,
@@ -410,7 +448,14 @@ impl NonFungibleTokenResolver for Contract {
             return true;
         };
 
-        log!("Return {} from @{} to @{}", token_id, receiver_id, owner_id);
+        NftEventKind::NftTransfer(vec![NftTransferLog {
+            authorized_id: None,
+            old_owner_id: receiver_id.clone(),
+            new_owner_id: owner_id.clone(),
+            token_ids: vec![token_id.clone()],
+            memo: None,
+        }])
+        .emit();
 
         self.internal_remove_token_from_owner(&receiver_id, &token_id);
         self.internal_add_token_to_owner(&owner_id, &token_id);
@@ -0,0 +1,237 @@
+use crate::*;
+use near_sdk::{ext_contract, Gas};
+
+/// CUSTOM - public mints of a token_type paid in NEAR (nft_mint_paid) or an FT (ft_transfer_call)
+/// the contract covers storage of FT mints, price them accordingly
+
+const GAS_FOR_FT_TRANSFER: Gas = 5_000_000_000_000;
+const GAS_FOR_RESOLVE_WITHDRAW_PROCEEDS: Gas = 10_000_000_000_000;
+const NO_DEPOSIT: Balance = 0;
+const DELIMETER: &str = "||";
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MintConfig {
+    /// metadata of every token minted with this config
+    pub metadata: TokenMetadata,
+    /// price per token in NEAR, can't be minted with NEAR without one
+    pub price: Option<U128>,
+    /// price per token by accepted FT contract, never 0 (the contract pays for storage of FT mints)
+    #[serde(default)]
+    pub ft_prices: HashMap<AccountId, U128>,
+    /// block timestamps (ms)
+    pub starts_at: Option<U64>,
+    pub ends_at: Option<U64>,
+    pub limit_per_account: Option<u32>,
+    pub max_per_tx: Option<u32>,
+}
+
+/// ft_transfer_call msg
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PaidMintArgs {
+    pub token_type: TokenType,
+    pub quantity: Option<u32>,
+}
+
+pub trait FungibleTokenReceiver {
+    fn ft_on_transfer(&mut self, sender_id: AccountId, amount: U128, msg: String) -> PromiseOrValue<U128>;
+}
+
+#[near_bindgen]
+impl FungibleTokenReceiver for Contract {
+    /// mints to the sender, anything above price * quantity is returned
+    fn ft_on_transfer(&mut self, sender_id: AccountId, amount: U128, msg: String) -> PromiseOrValue<U128> {
+        let PaidMintArgs { token_type, quantity } =
+            near_sdk::serde_json::from_str(&msg).expect("Invalid PaidMintArgs");
+        let ft_token_id = env::predecessor_account_id();
+        let config = self.mint_configs.get(&token_type).expect("No paid mint for token type");
+        let price = config
+            .ft_prices
+            .get(&ft_token_id)
+            .expect("Token type can't be minted with this FT")
+            .0;
+        assert!(price > 0, "FT price must be greater than 0");
+        let quantity = quantity.unwrap_or(1);
+        let cost = price * u128::from(quantity);
+        assert!(amount.0 >= cost, "Amount must cover price * quantity: {}", cost);
+
+        self.internal_paid_mint(&token_type, &config, &sender_id, quantity);
+        self.internal_add_mint_proceeds(&ft_token_id, cost);
+
+        PromiseOrValue::Value(U128(amount.0 - cost))
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// mints quantity (default 1) tokens to the caller, deposit covers price * quantity and storage, the rest is refunded
+    #[payable]
+    pub fn nft_mint_paid(&mut self, token_type: TokenType, quantity: Option<u32>) -> Vec<TokenId> {
+        let config = self.mint_configs.get(&token_type).expect("No paid mint for token type");
+        let price = config.price.expect("Token type can't be minted with NEAR").0;
+        let quantity = quantity.unwrap_or(1);
+        let owner_id = env::predecessor_account_id();
+
+        let initial_storage_usage = env::storage_usage();
+        let token_ids = self.internal_paid_mint(&token_type, &config, &owner_id, quantity);
+        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage
+            + self.extra_storage_in_bytes_per_token * u64::from(quantity);
+
+        let cost = price * u128::from(quantity);
+        let required_deposit = cost + env::storage_byte_cost() * Balance::from(required_storage_in_bytes);
+        let attached_deposit = env::attached_deposit();
+        assert!(
+            attached_deposit >= required_deposit,
+            "Must attach {} yoctoNEAR to cover price and storage",
+            required_deposit
+        );
+        self.internal_add_mint_proceeds(&"near".to_string(), cost);
+
+        let refund = attached_deposit - required_deposit;
+        if refund > 1 {
+            Promise::new(owner_id).transfer(refund);
+        }
+        token_ids
+    }
+
+    /// owner or admin, None stops paid mints of the token_type
+    pub fn set_mint_config(&mut self, token_type: TokenType, mint_config: Option<MintConfig>) {
+        self.assert_owner_or_admin();
+        assert!(self.supply_cap_by_type.contains_key(&token_type), "No token type {}", token_type);
+        if let Some(mint_config) = mint_config {
+            if let (Some(starts_at), Some(ends_at)) = (mint_config.starts_at, mint_config.ends_at) {
+                assert!(starts_at.0 < ends_at.0, "starts_at must be before ends_at");
+            }
+            // free FT mints would let anyone spend the contract's balance on storage
+            assert!(mint_config.ft_prices.values().all(|price| price.0 > 0), "FT price must be greater than 0");
+            self.mint_configs.insert(&token_type, &mint_config);
+        } else {
+            self.mint_configs.remove(&token_type);
+        }
+    }
+
+    /// only owner, pays out proceeds collected in NEAR ("near") or an FT to the owner
+    #[payable]
+    pub fn withdraw_mint_proceeds(&mut self, ft_token_id: ValidAccountId, amount: U128) -> Promise {
+        assert_one_yocto();
+        self.assert_owner();
+        let ft_token_id: AccountId = ft_token_id.into();
+        self.internal_withdraw_mint_proceeds(&ft_token_id, amount.0);
+        if ft_token_id == "near" {
+            Promise::new(self.owner_id.clone()).transfer(amount.0)
+        } else {
+            ext_fungible_token::ft_transfer(
+                self.owner_id.clone(),
+                amount,
+                None,
+                &ft_token_id,
+                1,
+                GAS_FOR_FT_TRANSFER,
+            )
+            .then(ext_self_proceeds::resolve_withdraw_mint_proceeds(
+                ft_token_id,
+                amount,
+                &env::current_account_id(),
+                NO_DEPOSIT,
+                GAS_FOR_RESOLVE_WITHDRAW_PROCEEDS,
+            ))
+        }
+    }
+
+    /// self callback, puts the proceeds back if the ft_transfer failed
+    #[private]
+    pub fn resolve_withdraw_mint_proceeds(&mut self, ft_token_id: AccountId, amount: U128) -> bool {
+        if near_sdk::is_promise_success() {
+            return true;
+        }
+        self.internal_add_mint_proceeds(&ft_token_id, amount.0);
+        false
+    }
+
+    /// views
+
+    pub fn get_mint_config(&self, token_type: TokenType) -> Option<MintConfig> {
+        self.mint_configs.get(&token_type)
+    }
+
+    pub fn get_paid_mints_for_account(&self, token_type: TokenType, account_id: ValidAccountId) -> u32 {
+        self.paid_mints_per_account
+            .get(&paid_mints_key(&token_type, account_id.as_ref()))
+            .unwrap_or(0)
+    }
+
+    pub fn get_mint_proceeds(&self, ft_token_id: AccountId) -> U128 {
+        U128(self.mint_proceeds.get(&ft_token_id).unwrap_or(0))
+    }
+}
+
+fn paid_mints_key(token_type: &TokenType, account_id: &AccountId) -> String {
+    format!("{}{}{}", token_type, DELIMETER, account_id)
+}
+
+impl Contract {
+    /// checks the mint window and limits, callers charge the price
+    pub(crate) fn internal_paid_mint(
+        &mut self,
+        token_type: &TokenType,
+        config: &MintConfig,
+        owner_id: &AccountId,
+        quantity: u32,
+    ) -> Vec<TokenId> {
+        assert!(quantity > 0, "Quantity must be greater than 0");
+        if let Some(max_per_tx) = config.max_per_tx {
+            assert!(quantity <= max_per_tx, "Cannot mint more than {} per transaction", max_per_tx);
+        }
+        let now = now_ms();
+        if let Some(starts_at) = config.starts_at {
+            assert!(now >= starts_at.0, "Mint has not started");
+        }
+        if let Some(ends_at) = config.ends_at {
+            assert!(now < ends_at.0, "Mint has ended");
+        }
+        let key = paid_mints_key(token_type, owner_id);
+        let minted = self.paid_mints_per_account.get(&key).unwrap_or(0) + quantity;
+        if let Some(limit_per_account) = config.limit_per_account {
+            assert!(minted <= limit_per_account, "Cannot mint more than {} per account", limit_per_account);
+        }
+        self.paid_mints_per_account.insert(&key, &minted);
+
+        (0..quantity)
+            .map(|_| {
+                self.internal_mint(
+                    None,
+                    config.metadata.clone(),
+                    HashMap::new(),
+                    owner_id.clone(),
+                    Some(token_type.clone()),
+                )
+            })
+            .collect()
+    }
+
+    pub(crate) fn internal_add_mint_proceeds(&mut self, ft_token_id: &AccountId, amount: Balance) {
+        let balance = self.mint_proceeds.get(ft_token_id).unwrap_or(0);
+        self.mint_proceeds.insert(ft_token_id, &(balance + amount));
+    }
+
+    pub(crate) fn internal_withdraw_mint_proceeds(&mut self, ft_token_id: &AccountId, amount: Balance) {
+        let balance = self.mint_proceeds.get(ft_token_id).unwrap_or(0);
+        assert!(amount > 0 && amount <= balance, "Not enough proceeds collected: {}", balance);
+        if balance == amount {
+            self.mint_proceeds.remove(ft_token_id);
+        } else {
+            self.mint_proceeds.insert(ft_token_id, &(balance - amount));
+        }
+    }
+}
+
+#[ext_contract(ext_fungible_token)]
+trait FungibleToken {
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+}
+
+#[ext_contract(ext_self_proceeds)]
+trait ExtSelfProceeds {
+    fn resolve_withdraw_mint_proceeds(&mut self, ft_token_id: AccountId, amount: U128) -> bool;
+}
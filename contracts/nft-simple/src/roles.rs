@@ -0,0 +1,120 @@
+use crate::*;
+
+/// CUSTOM - roles
+/// owner: everything, and the only one managing admins
+/// admins: lock, unlock and schedule unlocks of token types, manage minters, mint any token_type
+/// minters: mint any token_type, or only the token_types they were granted
+/// untyped mints are open to everyone while untyped_mints_open is set
+#[near_bindgen]
+impl Contract {
+    pub fn grant_admin(&mut self, account_id: ValidAccountId) -> bool {
+        self.assert_owner();
+        self.admins.insert(account_id.as_ref())
+    }
+
+    pub fn revoke_admin(&mut self, account_id: ValidAccountId) -> bool {
+        self.assert_owner();
+        self.admins.remove(account_id.as_ref())
+    }
+
+    /// owner or admin, without token_type the minter can mint any token_type
+    pub fn grant_minter(&mut self, account_id: ValidAccountId, token_type: Option<TokenType>) -> bool {
+        self.assert_owner_or_admin();
+        if let Some(token_type) = token_type {
+            assert!(self.supply_cap_by_type.contains_key(&token_type), "No token type {}", token_type);
+            let mut minters = self.minters_per_type.get(&token_type).unwrap_or_else(|| {
+                UnorderedSet::new(
+                    StorageKey::MintersPerTypeInner {
+                        token_type_hash: hash_account_id(&token_type),
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                )
+            });
+            let granted = minters.insert(account_id.as_ref());
+            self.minters_per_type.insert(&token_type, &minters);
+            granted
+        } else {
+            self.minters.insert(account_id.as_ref())
+        }
+    }
+
+    /// owner or admin
+    pub fn revoke_minter(&mut self, account_id: ValidAccountId, token_type: Option<TokenType>) -> bool {
+        self.assert_owner_or_admin();
+        if let Some(token_type) = token_type {
+            let mut minters = match self.minters_per_type.get(&token_type) {
+                Some(minters) => minters,
+                None => return false,
+            };
+            let revoked = minters.remove(account_id.as_ref());
+            if minters.is_empty() {
+                self.minters_per_type.remove(&token_type);
+            } else {
+                self.minters_per_type.insert(&token_type, &minters);
+            }
+            revoked
+        } else {
+            self.minters.remove(account_id.as_ref())
+        }
+    }
+
+    /// owner or admin
+    pub fn set_untyped_mints_open(&mut self, untyped_mints_open: bool) {
+        self.assert_owner_or_admin();
+        self.untyped_mints_open = untyped_mints_open;
+    }
+
+    /// views
+
+    pub fn get_admins(&self) -> Vec<AccountId> {
+        self.admins.to_vec()
+    }
+
+    pub fn is_admin(&self, account_id: ValidAccountId) -> bool {
+        self.admins.contains(account_id.as_ref())
+    }
+
+    /// minters of any token_type
+    pub fn get_minters(&self) -> Vec<AccountId> {
+        self.minters.to_vec()
+    }
+
+    pub fn get_minters_for_type(&self, token_type: TokenType) -> Vec<AccountId> {
+        self.minters_per_type
+            .get(&token_type)
+            .map(|minters| minters.to_vec())
+            .unwrap_or_default()
+    }
+
+    pub fn get_untyped_mints_open(&self) -> bool {
+        self.untyped_mints_open
+    }
+
+    pub fn can_mint(&self, account_id: ValidAccountId, token_type: Option<TokenType>) -> bool {
+        self.internal_can_mint(account_id.as_ref(), token_type.as_ref())
+    }
+}
+
+impl Contract {
+    pub(crate) fn assert_owner_or_admin(&self) {
+        let predecessor_account_id = env::predecessor_account_id();
+        assert!(
+            predecessor_account_id == self.owner_id || self.admins.contains(&predecessor_account_id),
+            "Owner or admin's method"
+        );
+    }
+
+    pub(crate) fn internal_can_mint(&self, account_id: &AccountId, token_type: Option<&TokenType>) -> bool {
+        if token_type.is_none() && self.untyped_mints_open {
+            return true;
+        }
+        if account_id == &self.owner_id || self.admins.contains(account_id) || self.minters.contains(account_id) {
+            return true;
+        }
+        token_type
+            .and_then(|token_type| self.minters_per_type.get(token_type))
+            .map(|minters| minters.contains(account_id))
+            .unwrap_or(false)
+    }
+}
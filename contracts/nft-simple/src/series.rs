@@ -0,0 +1,155 @@
+use crate::*;
+
+/// CUSTOM - series share one metadata template, editions mint as "{series_id}:{edition}"
+/// and only store the fields they override, nft_token merges both
+
+pub type SeriesId = u64;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Series {
+    pub metadata: TokenMetadata,
+    pub copies: u64,
+    /// price per edition in NEAR, without one only minters can mint
+    pub price: Option<U128>,
+    pub minted: u64,
+    pub royalty: HashMap<AccountId, u32>,
+    pub token_type: Option<TokenType>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonSeries {
+    pub series_id: U64,
+    #[serde(flatten)]
+    pub series: Series,
+}
+
+pub(crate) fn series_id_from_token_id(token_id: &TokenId) -> Option<SeriesId> {
+    let mut parts = token_id.splitn(2, ':');
+    let series_id = parts.next()?.parse().ok()?;
+    parts.next()?.parse::<u64>().ok()?;
+    Some(series_id)
+}
+
+#[near_bindgen]
+impl Contract {
+    /// only owner, deposit covers the template storage
+    #[payable]
+    pub fn create_series(
+        &mut self,
+        metadata: TokenMetadata,
+        copies: U64,
+        price: Option<U128>,
+        perpetual_royalties: Option<HashMap<AccountId, u32>>,
+        token_type: Option<TokenType>,
+    ) -> U64 {
+        self.assert_owner();
+        assert!(copies.0 > 0, "Series needs at least 1 copy");
+        if let Some(token_type) = &token_type {
+            assert!(self.supply_cap_by_type.contains_key(token_type), "No token type {}", token_type);
+        }
+        let initial_storage_usage = env::storage_usage();
+
+        let mut metadata = metadata;
+        metadata.copies = Some(copies.0);
+        let series_id = self.next_series_id;
+        self.next_series_id += 1;
+        self.series.insert(
+            &series_id,
+            &Series {
+                metadata,
+                copies: copies.0,
+                price,
+                minted: 0,
+                royalty: royalty_from_perpetual(perpetual_royalties),
+                token_type,
+            },
+        );
+
+        refund_deposit(env::storage_usage() - initial_storage_usage);
+        U64(series_id)
+    }
+
+    /// mints the next edition, anyone can mint series with a price (deposit covers price and storage), minters the rest
+    /// metadata only holds the fields that differ from the template, only minters can pass it
+    #[payable]
+    pub fn nft_mint_series(
+        &mut self,
+        series_id: U64,
+        metadata: Option<TokenMetadata>,
+        receiver_id: Option<ValidAccountId>,
+    ) -> TokenId {
+        let mut series = self.series.get(&series_id.0).expect("No series");
+        assert!(series.minted < series.copies, "Series is sold out");
+        let price = series.price.map(|price| price.0).unwrap_or(0);
+        let can_mint = self.internal_can_mint(&env::predecessor_account_id(), series.token_type.as_ref());
+        if series.price.is_none() {
+            assert!(can_mint, "Not allowed to mint this series");
+        }
+        // public buyers get the template as it is
+        let metadata = if can_mint { metadata.unwrap_or_default() } else { TokenMetadata::default() };
+
+        let initial_storage_usage = env::storage_usage();
+        series.minted += 1;
+        let token_id = format!("{}:{}", series_id.0, series.minted);
+        let owner_id = receiver_id.map(|a| a.into()).unwrap_or_else(env::predecessor_account_id);
+        self.internal_mint(
+            Some(token_id.clone()),
+            metadata,
+            series.royalty.clone(),
+            owner_id,
+            series.token_type.clone(),
+        );
+        self.series.insert(&series_id.0, &series);
+        let required_storage_in_bytes =
+            self.extra_storage_in_bytes_per_token + env::storage_usage() - initial_storage_usage;
+
+        let required_deposit = price + env::storage_byte_cost() * Balance::from(required_storage_in_bytes);
+        let attached_deposit = env::attached_deposit();
+        assert!(
+            attached_deposit >= required_deposit,
+            "Must attach {} yoctoNEAR to cover price and storage",
+            required_deposit
+        );
+        if price > 0 {
+            self.internal_add_mint_proceeds(&"near".to_string(), price);
+        }
+
+        let refund = attached_deposit - required_deposit;
+        if refund > 1 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+        token_id
+    }
+
+    /// views
+
+    pub fn get_series(&self, series_id: U64) -> Option<JsonSeries> {
+        self.series.get(&series_id.0).map(|series| JsonSeries { series_id, series })
+    }
+
+    pub fn get_series_list(&self, from_index: Option<U64>, limit: Option<u64>) -> Vec<JsonSeries> {
+        let start = u64::from(from_index.unwrap_or(U64(0)));
+        self.series
+            .iter()
+            .skip(start as usize)
+            .take(limit.unwrap_or(0) as usize)
+            .map(|(series_id, series)| JsonSeries {
+                series_id: U64(series_id),
+                series,
+            })
+            .collect()
+    }
+}
+
+impl Contract {
+    /// metadata of a token, series editions merge the template with their overrides
+    pub(crate) fn internal_token_metadata(&self, token_id: &TokenId) -> TokenMetadata {
+        let metadata = self.token_metadata_by_id.get(token_id).unwrap();
+        match series_id_from_token_id(token_id).and_then(|series_id| self.series.get(&series_id)) {
+            Some(series) => series.metadata.merged_with(metadata),
+            None => metadata,
+        }
+    }
+}
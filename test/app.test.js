@@ -23,6 +23,8 @@ jasmine.DEFAULT_TIMEOUT_INTERVAL = 60000;
 
 // this is used in creating the marketplace, tracks bids up to 3 most recent, default is 1
 const BID_HISTORY_LENGTH = 3;
+// sales are charged per byte and reserve storage for a full bid history in each FT, sellers deposit this per sale
+const storageSale = parseNearAmount('0.03');
 const DELIMETER = '||';
 
 const now = Date.now();
@@ -97,6 +99,18 @@ describe('deploy contract ' + contractName, () => {
 			gas: GAS
 		});
 
+		// alice and bob can mint the token types
+		for (const account_id of [aliceId, bobId]) {
+			for (const token_type of [tokenTypes[0], tokenTypes[1]]) {
+				await contractAccount.functionCall({
+					contractId,
+					methodName: 'grant_minter',
+					args: { account_id, token_type },
+					gas: GAS
+				});
+			}
+		}
+
 		/// create or get fungibleAccount and deploy ft.wasm (if not already deployed)
 		fungibleAccount = await createOrInitAccount(fungibleId, GUESTS_ACCOUNT_SECRET);
 		const fungibleAccountState = await fungibleAccount.state();
@@ -108,13 +122,15 @@ describe('deploy contract ' + contractName, () => {
 				/// will have totalSupply minted to them
 				owner_id: contractId,
 				total_supply: parseNearAmount('1000000'),
-				name: 'Test Fungible T',
-				symbol: 'TFT',
-				// not set by user request
-				version: '1',
-				reference: 'https://github.com/near/core-contracts/tree/master/w-near-141',
-				reference_hash: '7c879fa7b49901d0ecc6ff5d64d7f673da5e4a5eb52a8d50a214175760d8919a',
-				decimals: 24,
+				metadata: {
+					name: 'Test Fungible T',
+					symbol: 'TFT',
+					// not set by user request
+					version: '1',
+					reference: 'https://github.com/near/core-contracts/tree/master/w-near-141',
+					reference_hash: '7c879fa7b49901d0ecc6ff5d64d7f673da5e4a5eb52a8d50a214175760d8919a',
+					decimals: 24,
+				},
 			};
 			const actions = [
 				deployContract(fungibleContractBytes),
@@ -219,7 +235,7 @@ describe('deploy contract ' + contractName, () => {
 			methodName: 'storage_deposit',
 			args: {},
 			gas: GAS,
-			attachedDeposit: storageMarket
+			attachedDeposit: storageSale
 		});
 		await alice.functionCall({
 			contractId: contractId,
@@ -410,7 +426,7 @@ describe('deploy contract ' + contractName, () => {
 			methodName: 'storage_deposit',
 			args: {},
 			gas: GAS,
-			attachedDeposit: storageMarket
+			attachedDeposit: storageSale
 		});
 		await bob.functionCall({
 			contractId: fungibleId,
@@ -757,12 +773,12 @@ describe('deploy contract ' + contractName, () => {
 			methodName: 'storage_deposit',
 			args: {},
 			gas: GAS,
-			attachedDeposit: storageMarket
+			attachedDeposit: new BN(storageSale).mul(new BN(2)).toString()
 		});
 
-		/// bob just double paid for storage (check this)
+		/// bob paid for his first sale and these 2
 		const result = await contractAccount.viewFunction(marketId, 'storage_paid', { account_id: bobId });
-		expect(result).toEqual(parseNearAmount('0.02'));
+		expect(result).toEqual(parseNearAmount('0.09'));
 
 		await bob.functionCall({
 			contractId,
@@ -886,7 +902,7 @@ describe('deploy contract ' + contractName, () => {
 			methodName: 'storage_deposit',
 			args: {},
 			gas: GAS,
-			attachedDeposit: storageMarket
+			attachedDeposit: storageSale
 		});
 		await alice.functionCall({
 			contractId,